@@ -0,0 +1,58 @@
+//! Async API built on tokio, enabled with the `tokio` feature.
+
+use crate::{connect_host, Cert, CheckSSL, CheckSSLError};
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+
+impl CheckSSL {
+    /// Check ssl from domain with port 443 without blocking the current thread
+    ///
+    /// Example
+    ///
+    /// ```no_run
+    /// use checkssl::CheckSSL;
+    ///
+    /// # async fn run() {
+    /// let certificate = CheckSSL::from_domain_async("rust-lang.org").await.unwrap();
+    /// assert!(certificate.server.is_valid);
+    /// # }
+    /// ```
+    pub async fn from_domain_async(domain: &str) -> Result<Cert, CheckSSLError> {
+        let site = Self::server_name(domain)?;
+        let sock = TcpStream::connect((connect_host(domain)?.as_str(), 443))
+            .await
+            .map_err(CheckSSLError::Connect)?;
+
+        let (rc_config, verifier) = Self::tls_config(
+            Self::default_roots(),
+            false,
+            None,
+            rustls::DEFAULT_VERSIONS,
+            rustls::DEFAULT_CIPHER_SUITES,
+            None,
+        )?;
+        let tls = TlsConnector::from(Arc::new(rc_config))
+            .connect(site, sock)
+            .await?;
+        let (_, conn) = tls.get_ref();
+
+        Self::parse_connection(domain, conn, &verifier)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_ssl_async_server_is_valid() {
+        assert!(
+            CheckSSL::from_domain_async("rust-lang.org")
+                .await
+                .unwrap()
+                .server
+                .is_valid
+        );
+    }
+}
@@ -0,0 +1,388 @@
+//! DANE (RFC 6698): matching the chain against the TLSA records of the service, enabled with
+//! the `dane` feature.
+//!
+//! The records are asked to the system resolver, which has to validate DNSSEC: only answers
+//! it flags as authenticated are used.
+
+use crate::{ascii_domain, strip_brackets, subject_public_key_info};
+use ring::digest;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, UdpSocket};
+use std::time::Duration;
+use x509_parser::parse_x509_der;
+
+const TYPE_TLSA: u16 = 52;
+const CLASS_IN: u16 = 1;
+
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_TRUNCATED: u16 = 0x0200;
+const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+const FLAG_AUTHENTICATED: u16 = 0x0020;
+const RCODE_NXDOMAIN: u16 = 3;
+
+/// How long to wait for the resolver when the check has no timeout
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One TLSA record
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Tlsa {
+    /// 0 and 1 constrain a CA or the end-entity certificate of a PKIX valid chain, 2 and 3 name
+    /// a trust anchor or the end-entity certificate on their own
+    pub(crate) usage: u8,
+    /// 0 for the full certificate, 1 for its SubjectPublicKeyInfo
+    pub(crate) selector: u8,
+    /// 0 for the data itself, 1 for its SHA-256, 2 for its SHA-512
+    pub(crate) matching_type: u8,
+    pub(crate) data: Vec<u8>,
+}
+
+/// Records of an answer and whether the resolver authenticated them with DNSSEC
+#[derive(Debug, PartialEq)]
+struct Answer {
+    records: Vec<Tlsa>,
+    authenticated: bool,
+    truncated: bool,
+}
+
+/// Whether the chain matches one of `records`, for the PKIX usages only if `chain_trusted`
+pub(crate) fn verify(records: &[Tlsa], chain: &[rustls::Certificate], chain_trusted: bool) -> bool {
+    records
+        .iter()
+        .filter(|record| record.usage >= 2 || chain_trusted)
+        .any(|record| matches(record, chain))
+}
+
+fn matches(record: &Tlsa, chain: &[rustls::Certificate]) -> bool {
+    let candidates = match record.usage {
+        1 | 3 => &chain[..chain.len().min(1)],
+        0 | 2 => &chain[chain.len().min(1)..],
+        _ => return false,
+    };
+    candidates.iter().any(|certificate| match record.selector {
+        0 => matches_data(record, certificate.as_ref()),
+        1 => match parse_x509_der(certificate.as_ref()) {
+            Ok((_, x509cert)) => {
+                subject_public_key_info(&x509cert).is_some_and(|spki| matches_data(record, spki))
+            }
+            Err(_) => false,
+        },
+        _ => false,
+    })
+}
+
+fn matches_data(record: &Tlsa, selected: &[u8]) -> bool {
+    match record.matching_type {
+        0 => selected == record.data.as_slice(),
+        1 => digest::digest(&digest::SHA256, selected).as_ref() == record.data,
+        2 => digest::digest(&digest::SHA512, selected).as_ref() == record.data,
+        _ => false,
+    }
+}
+
+/// Authenticated TLSA records of `domain` on TCP `port`, asked to the first resolver of
+/// `/etc/resolv.conf`
+///
+/// An empty list means the service publishes none.
+pub(crate) fn lookup(
+    domain: &str,
+    port: u16,
+    timeout: Option<Duration>,
+) -> Result<Vec<Tlsa>, String> {
+    lookup_with(system_resolver()?, domain, port, timeout)
+}
+
+fn lookup_with(
+    resolver: SocketAddr,
+    domain: &str,
+    port: u16,
+    timeout: Option<Duration>,
+) -> Result<Vec<Tlsa>, String> {
+    if strip_brackets(domain).parse::<IpAddr>().is_ok() {
+        return Err("DANE needs a domain name, not an IP address".to_string());
+    }
+    let domain = ascii_domain(domain).map_err(|e| e.to_string())?;
+    let name = format!("_{}._tcp.{}", port, domain.trim_end_matches('.'));
+    let timeout = timeout.unwrap_or(DEFAULT_TIMEOUT);
+
+    let mut id = [0; 2];
+    SystemRandom::new()
+        .fill(&mut id)
+        .map_err(|_| "no randomness for the DNS query id".to_string())?;
+    let id = u16::from_be_bytes(id);
+    let message = query(id, &name);
+
+    let mut answer = parse_answer(&exchange_udp(resolver, &message, timeout)?, id)?;
+    if answer.truncated {
+        answer = parse_answer(&exchange_tcp(resolver, &message, timeout)?, id)?;
+    }
+    if !answer.records.is_empty() && !answer.authenticated {
+        return Err(format!(
+            "the TLSA records of {} are not authenticated with DNSSEC",
+            name
+        ));
+    }
+    Ok(answer.records)
+}
+
+fn system_resolver() -> Result<SocketAddr, String> {
+    let conf = std::fs::read_to_string("/etc/resolv.conf")
+        .map_err(|e| format!("cannot read /etc/resolv.conf: {}", e))?;
+    conf.lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .filter_map(|address| address.trim().parse::<IpAddr>().ok())
+        .map(|ip| SocketAddr::new(ip, 53))
+        .next()
+        .ok_or_else(|| "no nameserver in /etc/resolv.conf".to_string())
+}
+
+fn exchange_udp(
+    resolver: SocketAddr,
+    message: &[u8],
+    timeout: Duration,
+) -> Result<Vec<u8>, String> {
+    let local: SocketAddr = if resolver.is_ipv4() {
+        "0.0.0.0:0".parse().expect("valid address")
+    } else {
+        "[::]:0".parse().expect("valid address")
+    };
+    let sock = UdpSocket::bind(local).map_err(|e| e.to_string())?;
+    sock.set_read_timeout(Some(timeout))
+        .map_err(|e| e.to_string())?;
+    sock.connect(resolver).map_err(|e| e.to_string())?;
+    sock.send(message).map_err(|e| e.to_string())?;
+
+    let mut response = vec![0; 65535];
+    let len = sock
+        .recv(&mut response)
+        .map_err(|e| format!("no answer from the resolver {}: {}", resolver, e))?;
+    response.truncate(len);
+    Ok(response)
+}
+
+fn exchange_tcp(
+    resolver: SocketAddr,
+    message: &[u8],
+    timeout: Duration,
+) -> Result<Vec<u8>, String> {
+    let mut sock = TcpStream::connect_timeout(&resolver, timeout).map_err(|e| e.to_string())?;
+    sock.set_read_timeout(Some(timeout))
+        .map_err(|e| e.to_string())?;
+    // messages over TCP are prefixed with their length
+    let len = (message.len() as u16).to_be_bytes();
+    sock.write_all(&[&len[..], message].concat())
+        .map_err(|e| e.to_string())?;
+
+    let mut len = [0; 2];
+    sock.read_exact(&mut len).map_err(|e| e.to_string())?;
+    let mut response = vec![0; u16::from_be_bytes(len) as usize];
+    sock.read_exact(&mut response).map_err(|e| e.to_string())?;
+    Ok(response)
+}
+
+/// DNS query for the TLSA records of `name`, asking for recursion and the AD flag (RFC 6840)
+fn query(id: u16, name: &str) -> Vec<u8> {
+    let mut message = Vec::new();
+    message.extend_from_slice(&id.to_be_bytes());
+    message.extend_from_slice(&(FLAG_RECURSION_DESIRED | FLAG_AUTHENTICATED).to_be_bytes());
+    // one question, no answer, authority or additional record
+    message.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.split('.') {
+        message.push(label.len() as u8);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+    message.extend_from_slice(&TYPE_TLSA.to_be_bytes());
+    message.extend_from_slice(&CLASS_IN.to_be_bytes());
+    message
+}
+
+/// TLSA records of the answer section of the response to the query `id`
+///
+/// Other records, e.g. the CNAMEs leading to them, are skipped. A name that does not exist
+/// has no records.
+fn parse_answer(message: &[u8], id: u16) -> Result<Answer, String> {
+    let malformed = || "malformed DNS response".to_string();
+    let u16_at = |pos: usize| {
+        message
+            .get(pos..pos + 2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+            .ok_or_else(malformed)
+    };
+
+    if u16_at(0)? != id {
+        return Err("DNS response to another query".to_string());
+    }
+    let flags = u16_at(2)?;
+    if flags & FLAG_RESPONSE == 0 {
+        return Err(malformed());
+    }
+    let mut answer = Answer {
+        records: Vec::new(),
+        authenticated: flags & FLAG_AUTHENTICATED != 0,
+        truncated: flags & FLAG_TRUNCATED != 0,
+    };
+    match flags & 0x000f {
+        0 => {}
+        RCODE_NXDOMAIN => return Ok(answer),
+        rcode => return Err(format!("the resolver answered with error code {}", rcode)),
+    }
+
+    let questions = u16_at(4)?;
+    let answers = u16_at(6)?;
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(message, pos).ok_or_else(malformed)? + 4;
+    }
+    for _ in 0..answers {
+        pos = skip_name(message, pos).ok_or_else(malformed)?;
+        let record_type = u16_at(pos)?;
+        let len = u16_at(pos + 8)? as usize;
+        let data = message
+            .get(pos + 10..pos + 10 + len)
+            .ok_or_else(malformed)?;
+        pos += 10 + len;
+        if record_type == TYPE_TLSA && data.len() >= 3 {
+            answer.records.push(Tlsa {
+                usage: data[0],
+                selector: data[1],
+                matching_type: data[2],
+                data: data[3..].to_vec(),
+            });
+        }
+    }
+    Ok(answer)
+}
+
+/// Position after the possibly compressed name at `pos`
+fn skip_name(message: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *message.get(pos)?;
+        match len {
+            0 => return Some(pos + 1),
+            // a pointer ends the name
+            len if len & 0xc0 == 0xc0 => return Some(pos + 2),
+            len => pos += 1 + len as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server;
+    use std::thread;
+
+    /// Response to `query` with one answer per record, names compressed as resolvers do
+    fn response(query: &[u8], flags: u16, records: &[&[u8]]) -> Vec<u8> {
+        let mut message = query.to_vec();
+        message[2..4].copy_from_slice(&(FLAG_RESPONSE | flags).to_be_bytes());
+        message[6..8].copy_from_slice(&(records.len() as u16).to_be_bytes());
+        for data in records {
+            // pointer to the name of the question
+            message.extend_from_slice(&[0xc0, 12]);
+            message.extend_from_slice(&TYPE_TLSA.to_be_bytes());
+            message.extend_from_slice(&CLASS_IN.to_be_bytes());
+            message.extend_from_slice(&300u32.to_be_bytes());
+            message.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            message.extend_from_slice(data);
+        }
+        message
+    }
+
+    fn spki_sha256() -> Vec<u8> {
+        let certificate = test_server::certificate();
+        let (_, x509cert) = parse_x509_der(certificate.as_ref()).unwrap();
+        digest::digest(&digest::SHA256, subject_public_key_info(&x509cert).unwrap())
+            .as_ref()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_query() {
+        let message = query(0x1234, "_443._tcp.example.com");
+        assert_eq!(
+            &message[..12],
+            b"\x12\x34\x01\x20\x00\x01\x00\x00\x00\x00\x00\x00"
+        );
+        assert_eq!(
+            &message[12..],
+            &b"\x04_443\x04_tcp\x07example\x03com\x00\x00\x34\x00\x01"[..]
+        );
+    }
+
+    #[test]
+    fn test_parse_answer() {
+        let query = query(7, "_443._tcp.example.com");
+        let record = [&[3, 1, 1][..], &spki_sha256()].concat();
+        let message = response(&query, FLAG_AUTHENTICATED, &[&record]);
+
+        let answer = parse_answer(&message, 7).unwrap();
+        assert!(answer.authenticated);
+        assert!(!answer.truncated);
+        assert_eq!(
+            answer.records,
+            vec![Tlsa {
+                usage: 3,
+                selector: 1,
+                matching_type: 1,
+                data: spki_sha256(),
+            }]
+        );
+
+        assert!(parse_answer(&message, 8).is_err());
+        assert!(parse_answer(&message[..message.len() - 1], 7).is_err());
+        let nxdomain = response(&query, RCODE_NXDOMAIN, &[]);
+        assert_eq!(parse_answer(&nxdomain, 7).unwrap().records, vec![]);
+        let servfail = response(&query, 2, &[]);
+        assert!(parse_answer(&servfail, 7).is_err());
+    }
+
+    #[test]
+    fn test_verify() {
+        let chain = [test_server::certificate()];
+        let record = |usage, selector, matching_type, data: Vec<u8>| Tlsa {
+            usage,
+            selector,
+            matching_type,
+            data,
+        };
+        let full = chain[0].as_ref().to_vec();
+        let sha512 = digest::digest(&digest::SHA512, &full).as_ref().to_vec();
+
+        assert!(verify(&[record(3, 1, 1, spki_sha256())], &chain, false));
+        assert!(verify(&[record(3, 0, 0, full.clone())], &chain, false));
+        assert!(verify(&[record(3, 0, 2, sha512)], &chain, false));
+        assert!(!verify(&[record(3, 1, 1, vec![0; 32])], &chain, false));
+        // PKIX usages need a trusted chain
+        assert!(!verify(&[record(1, 0, 0, full.clone())], &chain, false));
+        assert!(verify(&[record(1, 0, 0, full.clone())], &chain, true));
+        // the end-entity certificate is not a trust anchor
+        assert!(!verify(&[record(2, 0, 0, full)], &chain, false));
+    }
+
+    #[test]
+    fn test_lookup_with() {
+        let resolver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = resolver.local_addr().unwrap();
+        let record = [&[3, 1, 1][..], &spki_sha256()].concat();
+        let server = thread::spawn(move || {
+            for flags in [FLAG_AUTHENTICATED, 0] {
+                let mut query = [0; 512];
+                let (len, client) = resolver.recv_from(&mut query).unwrap();
+                assert!(query[..len]
+                    .ends_with(b"\x04_853\x04_tcp\x07example\x03org\x00\x00\x34\x00\x01"));
+                let message = response(&query[..len], flags, &[&record]);
+                resolver.send_to(&message, client).unwrap();
+            }
+        });
+
+        let records = lookup_with(address, "example.org", 853, None).unwrap();
+        assert_eq!(records.len(), 1);
+        let actual = lookup_with(address, "example.org", 853, None);
+        assert!(actual.unwrap_err().contains("not authenticated"));
+        server.join().unwrap();
+
+        assert!(lookup_with(address, "127.0.0.1", 443, None).is_err());
+    }
+}
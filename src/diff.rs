@@ -0,0 +1,154 @@
+//! Field-level comparison of two checks, e.g. before and after a renewal.
+
+use crate::Cert;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// One field that differs between two [`Cert`]s, rendered as text
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CertDifference {
+    /// Path of the field, e.g. `server.sans`
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl fmt::Display for CertDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.field, self.old, self.new)
+    }
+}
+
+impl Cert {
+    /// Fields of the server certificate, its chain and the connection that differ in `other`
+    ///
+    /// Times derived from the moment of the check, like `days_until_expiration`, are left out.
+    ///
+    /// Example
+    ///
+    /// ```no_run
+    /// use checkssl::CheckSSL;
+    ///
+    /// let production = CheckSSL::from_domain("example.com").unwrap();
+    /// let staging = CheckSSL::from_domain("staging.example.com").unwrap();
+    /// for difference in production.diff(&staging) {
+    ///     println!("{}", difference);
+    /// }
+    /// ```
+    pub fn diff(&self, other: &Cert) -> Vec<CertDifference> {
+        let mut differences = Vec::new();
+        let mut compare = |field: &str, old: String, new: String| {
+            if old != new {
+                differences.push(CertDifference {
+                    field: field.to_string(),
+                    old,
+                    new,
+                });
+            }
+        };
+        let (old, new) = (&self.server, &other.server);
+
+        compare(
+            "server.subject_dn",
+            old.subject_dn.clone(),
+            new.subject_dn.clone(),
+        );
+        compare("server.sans", old.sans.join(", "), new.sans.join(", "));
+        compare(
+            "server.san_ip_addresses",
+            old.san_ip_addresses.join(", "),
+            new.san_ip_addresses.join(", "),
+        );
+        compare(
+            "server.issuer_dn",
+            old.issuer_dn.clone(),
+            new.issuer_dn.clone(),
+        );
+        compare(
+            "server.not_before",
+            old.not_before.to_rfc3339(),
+            new.not_before.to_rfc3339(),
+        );
+        compare(
+            "server.not_after",
+            old.not_after.to_rfc3339(),
+            new.not_after.to_rfc3339(),
+        );
+        compare(
+            "server.serial_number",
+            old.serial_number.clone(),
+            new.serial_number.clone(),
+        );
+        compare(
+            "server.sha256_fingerprint",
+            old.sha256_fingerprint.clone(),
+            new.sha256_fingerprint.clone(),
+        );
+        compare(
+            "server.signature_algorithm",
+            old.signature_algorithm.clone(),
+            new.signature_algorithm.clone(),
+        );
+        compare(
+            "server.public_key",
+            format!("{} {}", old.public_key_algorithm, old.public_key_bits),
+            format!("{} {}", new.public_key_algorithm, new.public_key_bits),
+        );
+
+        let chain = |cert: &Cert| {
+            cert.intermediates
+                .iter()
+                .map(|intermediate| intermediate.subject_dn.as_str())
+                .collect::<Vec<_>>()
+                .join(" / ")
+        };
+        compare("intermediates", chain(self), chain(other));
+        compare(
+            "chain_trusted",
+            self.chain_trusted.to_string(),
+            other.chain_trusted.to_string(),
+        );
+        compare(
+            "tls_version",
+            self.tls_version.clone(),
+            other.tls_version.clone(),
+        );
+        compare(
+            "cipher_suite",
+            self.cipher_suite.clone(),
+            other.cipher_suite.clone(),
+        );
+
+        differences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CheckSSL;
+
+    #[test]
+    fn test_diff() {
+        let old = CheckSSL::from_pem(include_bytes!("../tests/fixtures/chain.pem")).unwrap();
+        assert_eq!(old.diff(&old), vec![]);
+
+        let mut new = CheckSSL::from_pem(include_bytes!("../tests/fixtures/chain.pem")).unwrap();
+        new.server.sans.push("www.example.org".to_string());
+        new.server.not_after += chrono::Duration::days(90);
+        new.intermediates.clear();
+
+        let differences = old.diff(&new);
+        let fields: Vec<&str> = differences
+            .iter()
+            .map(|difference| difference.field.as_str())
+            .collect();
+        assert_eq!(
+            fields,
+            vec!["server.sans", "server.not_after", "intermediates"]
+        );
+        assert_eq!(
+            differences[0].to_string(),
+            "server.sans: example.com, *.example.com -> example.com, *.example.com, www.example.org"
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong while checking a certificate
+#[derive(Debug)]
+pub enum CheckSSLError {
+    /// Connecting to the server, or reading and writing on the connection, failed
+    Connect(io::Error),
+    /// The TLS handshake failed
+    #[cfg(feature = "net")]
+    Tls(rustls::Error),
+    /// The server refused or did not follow the plaintext StartTLS negotiation
+    StartTls(String),
+    /// A certificate presented by the server could not be parsed
+    Parse(String),
+    /// The server did not present any certificate, or sent an empty certificate list
+    NoCertificates,
+    /// The domain is neither a valid DNS name nor an IP address
+    InvalidServerName,
+    /// The proxy URL is invalid or the proxy did not open the tunnel
+    Proxy(String),
+    /// The server does not support the version required with `CheckSSLBuilder::min_tls_version`
+    TlsVersion(String),
+    /// The pin is malformed or no certificate presented by the server matches it
+    PinMismatch(String),
+    /// The URL given to `CheckSSL::from_url` is malformed or its scheme does not use TLS
+    InvalidUrl(String),
+    /// The value given to `CheckSSLBuilder::host_header` cannot be sent as a header
+    InvalidHostHeader(String),
+}
+
+impl CheckSSLError {
+    /// Name of the variant, for the serialized form
+    fn kind(&self) -> &'static str {
+        match self {
+            CheckSSLError::Connect(_) => "connect",
+            #[cfg(feature = "net")]
+            CheckSSLError::Tls(_) => "tls",
+            CheckSSLError::StartTls(_) => "start_tls",
+            CheckSSLError::Parse(_) => "parse",
+            CheckSSLError::NoCertificates => "no_certificates",
+            CheckSSLError::InvalidServerName => "invalid_server_name",
+            CheckSSLError::Proxy(_) => "proxy",
+            CheckSSLError::TlsVersion(_) => "tls_version",
+            CheckSSLError::PinMismatch(_) => "pin_mismatch",
+            CheckSSLError::InvalidUrl(_) => "invalid_url",
+            CheckSSLError::InvalidHostHeader(_) => "invalid_host_header",
+        }
+    }
+}
+
+impl fmt::Display for CheckSSLError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckSSLError::Connect(e) => write!(f, "connection failed: {}", e),
+            #[cfg(feature = "net")]
+            CheckSSLError::Tls(e) => write!(f, "TLS handshake failed: {}", e),
+            CheckSSLError::StartTls(message) => write!(f, "StartTLS failed: {}", message),
+            CheckSSLError::Parse(message) => write!(f, "certificate parsing failed: {}", message),
+            CheckSSLError::NoCertificates => write!(f, "certificate not found"),
+            CheckSSLError::InvalidServerName => write!(f, "invalid server name"),
+            CheckSSLError::Proxy(message) => write!(f, "proxy failed: {}", message),
+            CheckSSLError::TlsVersion(message) => write!(f, "TLS version refused: {}", message),
+            CheckSSLError::PinMismatch(message) => write!(f, "pin mismatch: {}", message),
+            CheckSSLError::InvalidUrl(message) => write!(f, "invalid URL: {}", message),
+            CheckSSLError::InvalidHostHeader(message) => {
+                write!(f, "invalid Host header: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CheckSSLError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CheckSSLError::Connect(e) => Some(e),
+            #[cfg(feature = "net")]
+            CheckSSLError::Tls(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Serializes as `{"kind": "connect", "message": "connection failed: ..."}`
+impl Serialize for CheckSSLError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("CheckSSLError", 2)?;
+        error.serialize_field("kind", self.kind())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
+
+impl From<io::Error> for CheckSSLError {
+    /// rustls reports handshake failures as `io::Error`s wrapping a `rustls::Error`, unwrap those
+    #[cfg(feature = "net")]
+    fn from(e: io::Error) -> Self {
+        match e
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<rustls::Error>())
+        {
+            Some(tls_error) => CheckSSLError::Tls(tls_error.clone()),
+            None => CheckSSLError::Connect(e),
+        }
+    }
+
+    #[cfg(not(feature = "net"))]
+    fn from(e: io::Error) -> Self {
+        CheckSSLError::Connect(e)
+    }
+}
+
+#[cfg(feature = "net")]
+impl From<rustls::Error> for CheckSSLError {
+    fn from(e: rustls::Error) -> Self {
+        CheckSSLError::Tls(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(
+            serde_json::to_value(CheckSSLError::InvalidServerName).unwrap(),
+            serde_json::json!({"kind": "invalid_server_name", "message": "invalid server name"})
+        );
+        assert_eq!(
+            serde_json::to_value(CheckSSLError::Parse("bad length".to_string())).unwrap(),
+            serde_json::json!({"kind": "parse", "message": "certificate parsing failed: bad length"})
+        );
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_from_io_error() {
+        let handshake = io::Error::new(
+            io::ErrorKind::InvalidData,
+            rustls::Error::AlertReceived(rustls::AlertDescription::HandshakeFailure),
+        );
+        assert!(matches!(
+            CheckSSLError::from(handshake),
+            CheckSSLError::Tls(rustls::Error::AlertReceived(_))
+        ));
+
+        let reset = io::Error::from(io::ErrorKind::ConnectionReset);
+        assert!(matches!(
+            CheckSSLError::from(reset),
+            CheckSSLError::Connect(e) if e.kind() == io::ErrorKind::ConnectionReset
+        ));
+    }
+}
@@ -0,0 +1,524 @@
+//! `checkssl` command line tool, built with the `cli` feature.
+//!
+//! Exits with 1 when the check fails or the server certificate is not currently valid,
+//! and with 2 on invalid arguments. With `--config`, the exit code is the one of the worst
+//! host, and 3 when a certificate is valid but expires within the warning threshold.
+//! `--jsonl` prints one JSON object per host as soon as it is checked instead of a single array,
+//! laid out like the lines of `CheckSSL::scan_to_writer` with the `port` and `status` of the host.
+
+use checkssl::{Cert, CheckSSL, CheckSSLBuilder, CheckSSLError};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+
+const USAGE: &str = "usage: checkssl [--port PORT] [--timeout SECONDS] [--json] DOMAIN\n\
+                     \x20      checkssl [--port PORT] [--timeout SECONDS] [--json | --jsonl] --config FILE";
+
+#[derive(Debug, PartialEq)]
+enum Target {
+    Domain(String),
+    /// TOML watchlist, see [`Config`]
+    Config(PathBuf),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Output {
+    Text,
+    Json,
+    /// One JSON object per line, the same as `Json` for a single domain
+    JsonLines,
+}
+
+#[derive(Debug, PartialEq)]
+struct Args {
+    target: Target,
+    /// Port of the domain, or of the hosts of the watchlist without one
+    port: u16,
+    timeout: Option<Duration>,
+    output: Output,
+}
+
+/// Watchlist read with `--config`
+///
+/// ```toml
+/// warn_days = 30
+///
+/// [[hosts]]
+/// domain = "example.com"
+///
+/// [[hosts]]
+/// domain = "mail.example.com"
+/// port = 993
+/// warn_days = 14
+/// ```
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// Days before expiration from which hosts without their own threshold are reported
+    warn_days: Option<i64>,
+    hosts: Vec<Host>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Host {
+    domain: String,
+    port: Option<u16>,
+    warn_days: Option<i64>,
+}
+
+/// Outcome of one host of the watchlist, from the best to the worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Ok,
+    Expiring,
+    Invalid,
+    Failed,
+}
+
+impl Status {
+    fn exit_code(self) -> i32 {
+        match self {
+            Status::Ok => 0,
+            Status::Expiring => 3,
+            Status::Invalid | Status::Failed => 1,
+        }
+    }
+}
+
+/// One line printed with `--config --jsonl`: a `CheckSSL::scan_to_writer` record with the port and status
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    domain: &'a str,
+    port: u16,
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a CheckSSLError>,
+    #[serde(flatten)]
+    cert: Option<&'a Cert>,
+}
+
+/// One element of the JSON array printed with `--config --json`
+#[derive(Serialize)]
+struct Report<'a> {
+    domain: &'a str,
+    port: u16,
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a CheckSSLError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cert: Option<&'a Cert>,
+}
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("checkssl: {}\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let domain = match &args.target {
+        Target::Domain(domain) => domain,
+        Target::Config(path) => {
+            let config = match read_config(path) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("checkssl: {}", e);
+                    process::exit(2);
+                }
+            };
+            process::exit(check_config(&args, &config).exit_code());
+        }
+    };
+
+    match builder(&args, args.port).check(domain) {
+        Ok(cert) => {
+            if args.output == Output::Text {
+                print!("{}", summary(domain, &cert));
+            } else {
+                println!("{}", cert.to_json().expect("Cert serializes to JSON"));
+            }
+            if !cert.server.is_valid {
+                process::exit(1);
+            }
+        }
+        Err(e) => {
+            eprintln!("checkssl: {}: {}", domain, e);
+            process::exit(1);
+        }
+    }
+}
+
+fn builder(args: &Args, port: u16) -> CheckSSLBuilder {
+    let builder = CheckSSLBuilder::new().port(port);
+    match args.timeout {
+        Some(timeout) => builder.timeout(timeout),
+        None => builder,
+    }
+}
+
+fn read_config(path: &Path) -> Result<Config, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    toml::from_str(&text).map_err(|e| format!("invalid config {}: {}", path.display(), e))
+}
+
+/// Check every host of the watchlist, print the results and return the worst status
+fn check_config(args: &Args, config: &Config) -> Status {
+    let ports: Vec<u16> = config
+        .hosts
+        .iter()
+        .map(|host| host.port.unwrap_or(args.port))
+        .collect();
+    let checks: Vec<(&str, CheckSSLBuilder)> = config
+        .hosts
+        .iter()
+        .zip(&ports)
+        .map(|(host, port)| (host.domain.as_str(), builder(args, *port)))
+        .collect();
+
+    match args.output {
+        Output::JsonLines => stream_json_lines(config, &ports, &checks),
+        Output::Text => report(config, &ports, &checks, |reports| {
+            print!("{}", table(reports))
+        }),
+        Output::Json => report(config, &ports, &checks, |reports| {
+            println!(
+                "{}",
+                serde_json::to_string(reports).expect("reports serialize to JSON")
+            )
+        }),
+    }
+}
+
+/// Print one JSON line per host as soon as its check completes
+fn stream_json_lines(config: &Config, ports: &[u16], checks: &[(&str, CheckSSLBuilder)]) -> Status {
+    let mut worst = Status::Ok;
+    CheckSSL::scan_builders(checks, |index, result| {
+        let status = status(result.as_ref().ok(), warn_days(config, index));
+        worst = worst.max(status);
+        println!(
+            "{}",
+            json_line(&config.hosts[index].domain, ports[index], status, &result)
+        );
+    });
+    worst
+}
+
+/// Wait for every check, then `print` the reports in the order of the watchlist
+fn report(
+    config: &Config,
+    ports: &[u16],
+    checks: &[(&str, CheckSSLBuilder)],
+    print: impl FnOnce(&[Report]),
+) -> Status {
+    let results = CheckSSL::from_builders(checks);
+
+    let reports: Vec<Report> = ports
+        .iter()
+        .zip(&results)
+        .enumerate()
+        .map(|(index, (port, (domain, result)))| {
+            let (error, cert) = match result {
+                Ok(cert) => (None, Some(cert)),
+                Err(e) => (Some(e), None),
+            };
+            Report {
+                domain,
+                port: *port,
+                status: status(result.as_ref().ok(), warn_days(config, index)),
+                error,
+                cert,
+            }
+        })
+        .collect();
+
+    print(&reports);
+    reports
+        .iter()
+        .map(|report| report.status)
+        .max()
+        .unwrap_or(Status::Ok)
+}
+
+/// Warning threshold of the host at `index`, its own or the one of the whole watchlist
+fn warn_days(config: &Config, index: usize) -> Option<i64> {
+    config.hosts[index].warn_days.or(config.warn_days)
+}
+
+fn status(cert: Option<&Cert>, warn_days: Option<i64>) -> Status {
+    match cert {
+        None => Status::Failed,
+        Some(cert) if !cert.server.is_valid => Status::Invalid,
+        Some(cert) if warn_days.is_some_and(|days| cert.server.days_until_expiration < days) => {
+            Status::Expiring
+        }
+        Some(_) => Status::Ok,
+    }
+}
+
+fn json_line(
+    domain: &str,
+    port: u16,
+    status: Status,
+    result: &Result<Cert, CheckSSLError>,
+) -> String {
+    let line = JsonLine {
+        domain,
+        port,
+        status,
+        error: result.as_ref().err(),
+        cert: result.as_ref().ok(),
+    };
+    serde_json::to_string(&line).expect("lines serialize to JSON")
+}
+
+/// One line per host, aligned on the longest domain
+fn table(reports: &[Report]) -> String {
+    let width = reports
+        .iter()
+        .map(|report| report.domain.len())
+        .chain(std::iter::once("DOMAIN".len()))
+        .max()
+        .unwrap_or(0);
+    let mut table = format!("{:width$}  PORT   DAYS  STATUS\n", "DOMAIN", width = width);
+    for report in reports {
+        let days = report
+            .cert
+            .map(|cert| cert.server.days_until_expiration.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let status = match report.error {
+            Some(e) => format!("failed: {}", e),
+            None => format!("{:?}", report.status).to_lowercase(),
+        };
+        table.push_str(&format!(
+            "{:width$}  {:<5}  {:>4}  {}\n",
+            report.domain,
+            report.port,
+            days,
+            status,
+            width = width
+        ));
+    }
+    table
+}
+
+/// Parse the command line, `None` when help was asked for
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Option<Args>, String> {
+    let mut domain = None;
+    let mut config = None;
+    let mut port = 443;
+    let mut timeout = None;
+    let mut output = Output::Text;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--json" => output = Output::Json,
+            "--jsonl" => output = Output::JsonLines,
+            "--config" | "-c" => {
+                let value = args.next().ok_or("--config needs a value")?;
+                config = Some(PathBuf::from(value));
+            }
+            "--port" | "-p" => {
+                let value = args.next().ok_or("--port needs a value")?;
+                port = value
+                    .parse()
+                    .map_err(|_| format!("invalid port: {}", value))?;
+            }
+            "--timeout" | "-t" => {
+                let value = args.next().ok_or("--timeout needs a value")?;
+                let seconds: f64 = value
+                    .parse()
+                    .ok()
+                    .filter(|seconds: &f64| seconds.is_finite() && *seconds > 0.0)
+                    .ok_or_else(|| format!("invalid timeout: {}", value))?;
+                timeout = Some(Duration::from_secs_f64(seconds));
+            }
+            flag if flag.starts_with('-') => return Err(format!("unknown option: {}", flag)),
+            _ if domain.is_some() => return Err(format!("unexpected argument: {}", arg)),
+            _ => domain = Some(arg),
+        }
+    }
+
+    let target = match (domain, config) {
+        (Some(_), Some(_)) => return Err("--config cannot be used with a domain".to_string()),
+        (Some(domain), None) => Target::Domain(domain),
+        (None, Some(config)) => Target::Config(config),
+        (None, None) => return Err("missing domain".to_string()),
+    };
+    Ok(Some(Args {
+        target,
+        port,
+        timeout,
+        output,
+    }))
+}
+
+/// Human readable report of the server certificate
+fn summary(domain: &str, cert: &Cert) -> String {
+    let yes_no = |value: bool| if value { "yes" } else { "no" };
+
+    format!(
+        "{domain}\n\
+         \x20 subject:        {subject}\n\
+         \x20 issuer:         {issuer}\n\
+         \x20 names:          {sans}\n\
+         \x20 not before:     {not_before}\n\
+         \x20 not after:      {not_after} ({days} day(s) left)\n\
+         \x20 valid:          {valid}\n\
+         \x20 hostname match: {hostname_matches}\n\
+         \x20 chain trusted:  {chain_trusted}\n\
+         \x20 protocol:       {tls_version} {cipher_suite}\n",
+        domain = domain,
+        subject = cert.server.subject_dn,
+        issuer = cert.server.issuer_dn,
+        sans = cert.server.sans.join(", "),
+        not_before = cert.server.not_before,
+        not_after = cert.server.not_after,
+        days = cert.server.days_until_expiration,
+        valid = yes_no(cert.server.is_valid),
+        hostname_matches = yes_no(cert.server.hostname_matches),
+        chain_trusted = match &cert.chain_error {
+            Some(e) => format!("no ({})", e),
+            None => yes_no(cert.chain_trusted).to_string(),
+        },
+        tls_version = cert.tls_version,
+        cipher_suite = cert.cipher_suite,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            parse(&[
+                "--port",
+                "8443",
+                "example.com",
+                "--json",
+                "--timeout",
+                "2.5"
+            ]),
+            Ok(Some(Args {
+                target: Target::Domain("example.com".to_string()),
+                port: 8443,
+                timeout: Some(Duration::from_millis(2500)),
+                output: Output::Json,
+            }))
+        );
+        assert_eq!(parse(&["--help"]), Ok(None));
+        assert_eq!(
+            parse(&["--config", "hosts.toml"]),
+            Ok(Some(Args {
+                target: Target::Config(PathBuf::from("hosts.toml")),
+                port: 443,
+                timeout: None,
+                output: Output::Text,
+            }))
+        );
+        assert_eq!(
+            parse(&["--jsonl", "--config", "hosts.toml"])
+                .unwrap()
+                .unwrap()
+                .output,
+            Output::JsonLines
+        );
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["--port", "https", "example.com"]).is_err());
+        assert!(parse(&["--timeout", "0", "example.com"]).is_err());
+        assert!(parse(&["--verbose", "example.com"]).is_err());
+        assert!(parse(&["example.com", "example.org"]).is_err());
+        assert!(parse(&["--config", "hosts.toml", "example.com"]).is_err());
+        assert!(parse(&["--config"]).is_err());
+    }
+
+    #[test]
+    fn test_config() {
+        let config: Config = toml::from_str(
+            "warn_days = 30\n\
+             [[hosts]]\n\
+             domain = \"example.com\"\n\
+             [[hosts]]\n\
+             domain = \"mail.example.com\"\n\
+             port = 993\n\
+             warn_days = 14\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                warn_days: Some(30),
+                hosts: vec![
+                    Host {
+                        domain: "example.com".to_string(),
+                        port: None,
+                        warn_days: None,
+                    },
+                    Host {
+                        domain: "mail.example.com".to_string(),
+                        port: Some(993),
+                        warn_days: Some(14),
+                    },
+                ],
+            }
+        );
+        assert!(toml::from_str::<Config>("[[hosts]]\nname = \"example.com\"\n").is_err());
+    }
+
+    #[test]
+    fn test_json_line() {
+        let cert = CheckSSL::from_pem(include_bytes!("../tests/fixtures/chain.pem")).unwrap();
+
+        let line: serde_json::Value =
+            serde_json::from_str(&json_line("example.com", 443, Status::Ok, &Ok(cert))).unwrap();
+        assert_eq!(line["domain"], "example.com");
+        assert_eq!(line["port"], 443);
+        assert_eq!(line["status"], "ok");
+        // flattened, as in `CheckSSL::scan_to_writer`
+        assert_eq!(line["server"]["common_name"], "example.com");
+        assert!(line.get("error").is_none());
+
+        let error = Err(CheckSSLError::InvalidServerName);
+        let line: serde_json::Value =
+            serde_json::from_str(&json_line("", 993, Status::Failed, &error)).unwrap();
+        assert_eq!(line["port"], 993);
+        assert_eq!(line["status"], "failed");
+        assert_eq!(line["error"]["kind"], "invalid_server_name");
+        assert!(line.get("server").is_none());
+    }
+
+    #[test]
+    fn test_status() {
+        let mut cert = CheckSSL::from_pem(include_bytes!("../tests/fixtures/chain.pem")).unwrap();
+        cert.server.days_until_expiration = 10;
+        assert_eq!(status(Some(&cert), None), Status::Ok);
+        assert_eq!(status(Some(&cert), Some(5)), Status::Ok);
+        assert_eq!(status(Some(&cert), Some(30)), Status::Expiring);
+        cert.server.is_valid = false;
+        assert_eq!(status(Some(&cert), Some(30)), Status::Invalid);
+        assert_eq!(status(None, None), Status::Failed);
+
+        assert!(Status::Failed > Status::Expiring);
+        assert_eq!(Status::Expiring.exit_code(), 3);
+    }
+}
@@ -0,0 +1,90 @@
+//! OpenSSL backend, enabled with the `native-tls` feature.
+//!
+//! The chain is verified against the system trust store, as curl does, and parsed like the
+//! rustls one so that callers get the same `Cert` whatever the backend.
+
+use crate::{connect_host, hostname_matches, strip_brackets, Cert, CheckSSL, CheckSSLError};
+use chrono::Utc;
+use openssl::ssl::{HandshakeError, SslConnector, SslMethod, SslVerifyMode};
+use openssl::x509::X509VerifyResult;
+use std::net::TcpStream;
+
+/// Handshake with OpenSSL on `port` and parse the chain the server presents, also given back as DER
+pub(crate) fn check(domain: &str, port: u16) -> Result<(Cert, Vec<Vec<u8>>), CheckSSLError> {
+    // rejects the same names as the rustls backend
+    CheckSSL::server_name(domain)?;
+    let host = connect_host(domain)?;
+    let mut builder = SslConnector::builder(SslMethod::tls_client()).map_err(tls_error)?;
+    // verified anyway, the outcome goes to `Cert::chain_trusted` as with rustls
+    builder.set_verify(SslVerifyMode::NONE);
+    let connector = builder.build();
+
+    let sock = TcpStream::connect((host.as_str(), port)).map_err(CheckSSLError::Connect)?;
+    // skips SNI for IP addresses and checks the name during the verification
+    let stream = connector
+        .connect(strip_brackets(&host), sock)
+        .map_err(|e| match e {
+            HandshakeError::SetupFailure(e) => tls_error(e),
+            HandshakeError::Failure(mid) | HandshakeError::WouldBlock(mid) => {
+                match mid.into_error().into_io_error() {
+                    Ok(e) => CheckSSLError::Connect(e),
+                    Err(e) => tls_error(e),
+                }
+            }
+        })?;
+    let ssl = stream.ssl();
+
+    let certificates = match ssl.peer_cert_chain() {
+        Some(chain) => chain
+            .iter()
+            .map(|certificate| certificate.to_der())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(tls_error)?,
+        None => return Err(CheckSSLError::NoCertificates),
+    };
+
+    let mut cert = CheckSSL::parse_chain(&certificates, Utc::now())?;
+    cert.server.hostname_matches = hostname_matches(domain, &cert.server);
+    cert.tls_version = ssl.version_str().to_string();
+    if let Some(cipher) = ssl.current_cipher() {
+        cert.cipher_suite = cipher.standard_name().unwrap_or(cipher.name()).to_string();
+    }
+    cert.negotiated_alpn = ssl
+        .selected_alpn_protocol()
+        .map(|protocol| String::from_utf8_lossy(protocol).into_owned());
+
+    let verified = ssl.verify_result();
+    cert.chain_trusted = verified == X509VerifyResult::OK;
+    if !cert.chain_trusted {
+        cert.chain_error = Some(verified.error_string().to_string());
+    }
+    Ok((cert, certificates))
+}
+
+fn tls_error<E: std::fmt::Display>(e: E) -> CheckSSLError {
+    CheckSSLError::Tls(rustls::Error::General(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server;
+
+    #[test]
+    fn test_check() {
+        let (port, server) = test_server::serve(test_server::config());
+
+        let (cert, chain) = check("127.0.0.1", port).unwrap();
+        assert_eq!(
+            chain,
+            [include_bytes!("../tests/fixtures/server.der").to_vec()]
+        );
+        assert_eq!(cert.server.common_name, "localhost");
+        assert!(cert.server.hostname_matches);
+        assert_eq!(cert.tls_version, "TLSv1.3");
+        // self-signed, so not in the system trust store
+        assert!(!cert.chain_trusted);
+        assert!(cert.chain_error.is_some());
+        server.join().unwrap();
+    }
+}
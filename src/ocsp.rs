@@ -0,0 +1,435 @@
+//! Reading OCSP responses (RFC 6960).
+
+use crate::tlv::split_tlv;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "revocation")]
+use {
+    crate::builder::{connect, AddressFamily},
+    crate::tlv::encode_tlv,
+    ring::digest,
+    std::io::{Read, Write},
+    std::time::Duration,
+    x509_parser::objects::OID_ACCESSDESCRIPTOR_OCSP,
+    x509_parser::{parse_x509_der, X509Certificate},
+};
+
+const SEQUENCE: u8 = 0x30;
+const ENUMERATED: u8 = 0x0a;
+const OCTET_STRING: u8 = 0x04;
+const OID: u8 = 0x06;
+const INTEGER: u8 = 0x02;
+const EXPLICIT_0: u8 = 0xa0;
+#[cfg(feature = "revocation")]
+const NULL: u8 = 0x05;
+
+/// `id-sha1`, 1.3.14.3.2.26
+#[cfg(feature = "revocation")]
+const SHA1: &[u8] = b"\x2b\x0e\x03\x02\x1a";
+
+/// Largest HTTP response read from an OCSP responder, headers included
+#[cfg(feature = "revocation")]
+const MAX_OCSP_RESPONSE: u64 = 64 * 1024;
+
+/// How long to wait for the responder when the check has no timeout
+#[cfg(feature = "revocation")]
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// `id-pkix-ocsp-basic`, 1.3.6.1.5.5.7.48.1.1
+const OCSP_BASIC: &[u8] = b"\x2b\x06\x01\x05\x05\x07\x30\x01\x01";
+
+/// Revocation status of a certificate according to its OCSP responder
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OcspStatus {
+    Good,
+    Revoked,
+    Unknown,
+}
+
+/// Status of the certificate with the DER integer `serial` in a DER encoded `OCSPResponse`
+///
+/// `None` when the response is malformed, not successful or does not cover the certificate.
+pub(crate) fn certificate_status(response: &[u8], serial: &[u8]) -> Option<OcspStatus> {
+    let response = expect(SEQUENCE, response)?.0;
+    let (status, rest) = expect(ENUMERATED, response)?;
+    if status != [0x00] {
+        // malformedRequest, tryLater, unauthorized, ...
+        return None;
+    }
+
+    let response_bytes = expect(SEQUENCE, expect(EXPLICIT_0, rest)?.0)?.0;
+    let (response_type, rest) = expect(OID, response_bytes)?;
+    if response_type != OCSP_BASIC {
+        return None;
+    }
+    let basic = expect(SEQUENCE, expect(OCTET_STRING, rest)?.0)?.0;
+    let response_data = expect(SEQUENCE, basic)?.0;
+
+    // skip the optional version, the responder id and producedAt
+    let (tag, _, mut rest) = split_tlv(response_data)?;
+    if tag == EXPLICIT_0 {
+        rest = split_tlv(rest)?.2;
+    }
+    let rest = split_tlv(rest)?.2;
+
+    let mut responses = expect(SEQUENCE, rest)?.0;
+    while !responses.is_empty() {
+        let (single, next) = expect(SEQUENCE, responses)?;
+        responses = next;
+
+        let (cert_id, rest) = expect(SEQUENCE, single)?;
+        // hashAlgorithm, issuerNameHash and issuerKeyHash come before the serial number
+        let cert_id = split_tlv(split_tlv(split_tlv(cert_id)?.2)?.2)?.2;
+        if expect(INTEGER, cert_id)?.0 != serial {
+            continue;
+        }
+
+        return match split_tlv(rest)?.0 {
+            0x80 => Some(OcspStatus::Good),
+            0xa1 => Some(OcspStatus::Revoked),
+            0x82 => Some(OcspStatus::Unknown),
+            _ => None,
+        };
+    }
+    None
+}
+
+/// Ask the OCSP responder named in the leaf of `chain` about its revocation status
+///
+/// The issuer must be part of the chain. The signature of the response is not checked, as for
+/// stapled responses.
+#[cfg(feature = "revocation")]
+pub(crate) fn query(
+    chain: &[rustls::Certificate],
+    timeout: Option<Duration>,
+) -> Result<OcspStatus, String> {
+    fn parse(certificate: &rustls::Certificate) -> Result<X509Certificate<'_>, String> {
+        parse_x509_der(certificate.as_ref())
+            .map(|(_, x509cert)| x509cert)
+            .map_err(|e| e.to_string())
+    }
+    let leaf = parse(chain.first().ok_or("no certificate")?)?;
+    let url = crate::access_urls(&leaf, &OID_ACCESSDESCRIPTOR_OCSP)
+        .into_iter()
+        .find(|url| url.to_ascii_lowercase().starts_with("http://"))
+        .ok_or("no HTTP OCSP responder in the certificate")?;
+
+    for certificate in chain {
+        let issuer = parse(certificate)?;
+        if issuer.tbs_certificate.subject.as_raw() == leaf.tbs_certificate.issuer.as_raw() {
+            return query_responder(&url, &leaf, &issuer, timeout);
+        }
+    }
+    Err("the issuer certificate was not sent by the server".to_string())
+}
+
+#[cfg(feature = "revocation")]
+fn query_responder(
+    url: &str,
+    leaf: &X509Certificate,
+    issuer: &X509Certificate,
+    timeout: Option<Duration>,
+) -> Result<OcspStatus, String> {
+    let response = post(url, &request(leaf, issuer), timeout)?;
+    certificate_status(&response, leaf.tbs_certificate.raw_serial())
+        .ok_or_else(|| "the OCSP response has no status for the certificate".to_string())
+}
+
+/// DER encoded `OCSPRequest` about `leaf`, with SHA-1 hashes as most responders expect
+#[cfg(feature = "revocation")]
+fn request(leaf: &X509Certificate, issuer: &X509Certificate) -> Vec<u8> {
+    let sha1 = |data: &[u8]| digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, data);
+
+    let hash_algorithm = [encode_tlv(OID, SHA1), encode_tlv(NULL, &[])].concat();
+    let cert_id = [
+        encode_tlv(SEQUENCE, &hash_algorithm),
+        encode_tlv(
+            OCTET_STRING,
+            sha1(leaf.tbs_certificate.issuer.as_raw()).as_ref(),
+        ),
+        encode_tlv(
+            OCTET_STRING,
+            sha1(issuer.tbs_certificate.subject_pki.subject_public_key.data).as_ref(),
+        ),
+        encode_tlv(INTEGER, leaf.tbs_certificate.raw_serial()),
+    ]
+    .concat();
+
+    // OCSPRequest, TBSRequest, requestList and Request all wrap the single CertID
+    (0..4).fold(encode_tlv(SEQUENCE, &cert_id), |inner, _| {
+        encode_tlv(SEQUENCE, &inner)
+    })
+}
+
+/// POST `body` to the `http://` `url` and return the body of the response
+#[cfg(feature = "revocation")]
+fn post(url: &str, body: &[u8], timeout: Option<Duration>) -> Result<Vec<u8>, String> {
+    let rest = &url["http://".len()..];
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let port_start = authority
+        .rfind(':')
+        .filter(|&i| !authority[i..].contains(']'));
+    let (host, port) = match port_start {
+        Some(i) => (
+            &authority[..i],
+            authority[i + 1..]
+                .parse()
+                .map_err(|_| format!("invalid port in {}", url))?,
+        ),
+        None => (authority, 80),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    let timeout = Some(timeout.unwrap_or(DEFAULT_TIMEOUT));
+    let mut sock =
+        connect(host, port, timeout, None, AddressFamily::Any).map_err(|e| e.to_string())?;
+    let head = format!(
+        "POST {} HTTP/1.0\r\nHost: {}\r\nContent-Type: application/ocsp-request\r\n\
+         Content-Length: {}\r\n\r\n",
+        path,
+        authority,
+        body.len()
+    );
+    sock.write_all(&[head.as_bytes(), body].concat())
+        .map_err(|e| e.to_string())?;
+
+    let mut response = Vec::new();
+    // one byte more than allowed, to tell a response of exactly the limit from a larger one
+    sock.take(MAX_OCSP_RESPONSE + 1)
+        .read_to_end(&mut response)
+        .map_err(|e| e.to_string())?;
+    if response.len() as u64 > MAX_OCSP_RESPONSE {
+        return Err(format!(
+            "the OCSP response is larger than {} bytes",
+            MAX_OCSP_RESPONSE
+        ));
+    }
+    let body_start = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or("truncated HTTP response from the OCSP responder")?;
+    let status = String::from_utf8_lossy(&response[..body_start]);
+    let status = status.lines().next().unwrap_or_default();
+    if status.split(' ').nth(1) != Some("200") {
+        return Err(format!("OCSP responder answered {}", status));
+    }
+    Ok(response[body_start + 4..].to_vec())
+}
+
+/// Split off the first element, as `(value, rest)`, if it has the `tag` we expect
+fn expect(tag: u8, input: &[u8]) -> Option<(&[u8], &[u8])> {
+    match split_tlv(input)? {
+        (actual, value, rest) if actual == tag => Some((value, rest)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::tlv::encode_tlv as tlv;
+
+    /// A successful basic OCSP response with one single response per `(serial, status tag)`
+    ///
+    /// Signature and hashes are dummies, only the structure matters here.
+    pub(crate) fn response(singles: &[(&[u8], u8)]) -> Vec<u8> {
+        let singles: Vec<u8> = singles
+            .iter()
+            .flat_map(|(serial, status)| {
+                let cert_id = [
+                    tlv(SEQUENCE, &tlv(OID, b"\x2b\x0e\x03\x02\x1a")),
+                    tlv(OCTET_STRING, &[1; 20]),
+                    tlv(OCTET_STRING, &[2; 20]),
+                    tlv(INTEGER, serial),
+                ]
+                .concat();
+                let status = if *status == 0xa1 {
+                    tlv(*status, &tlv(0x18, b"20200101000000Z"))
+                } else {
+                    tlv(*status, &[])
+                };
+                tlv(
+                    SEQUENCE,
+                    &[
+                        tlv(SEQUENCE, &cert_id),
+                        status,
+                        tlv(0x18, b"20200101000000Z"),
+                    ]
+                    .concat(),
+                )
+            })
+            .collect();
+
+        let response_data = tlv(
+            SEQUENCE,
+            &[
+                tlv(0xa2, &tlv(OCTET_STRING, &[3; 20])),
+                tlv(0x18, b"20200101000000Z"),
+                tlv(SEQUENCE, &singles),
+            ]
+            .concat(),
+        );
+        let basic = tlv(
+            SEQUENCE,
+            &[
+                response_data,
+                tlv(SEQUENCE, &tlv(OID, b"\x2a\x86\x48\xce\x3d\x04\x03\x02")),
+                tlv(0x03, &[0, 0]),
+            ]
+            .concat(),
+        );
+        tlv(
+            SEQUENCE,
+            &[
+                tlv(ENUMERATED, &[0]),
+                tlv(
+                    EXPLICIT_0,
+                    &tlv(
+                        SEQUENCE,
+                        &[tlv(OID, OCSP_BASIC), tlv(OCTET_STRING, &basic)].concat(),
+                    ),
+                ),
+            ]
+            .concat(),
+        )
+    }
+
+    #[test]
+    fn test_certificate_status() {
+        let response = response(&[(b"\x01", 0xa1), (b"\x02", 0x80), (b"\x03", 0x82)]);
+
+        assert_eq!(
+            certificate_status(&response, b"\x01"),
+            Some(OcspStatus::Revoked)
+        );
+        assert_eq!(
+            certificate_status(&response, b"\x02"),
+            Some(OcspStatus::Good)
+        );
+        assert_eq!(
+            certificate_status(&response, b"\x03"),
+            Some(OcspStatus::Unknown)
+        );
+        assert_eq!(certificate_status(&response, b"\x04"), None);
+    }
+
+    #[test]
+    fn test_certificate_status_unsuccessful() {
+        // tryLater
+        assert_eq!(certificate_status(b"\x30\x03\x0a\x01\x03", b"\x01"), None);
+        assert_eq!(certificate_status(b"garbage", b"\x01"), None);
+    }
+
+    /// DER of the fixture leaf and of the intermediate that issued it
+    #[cfg(feature = "revocation")]
+    fn chain() -> Vec<Vec<u8>> {
+        vec![
+            include_bytes!("../tests/fixtures/leaf.der").to_vec(),
+            include_bytes!("../tests/fixtures/intermediate-root1.der").to_vec(),
+        ]
+    }
+
+    #[cfg(feature = "revocation")]
+    #[test]
+    fn test_query_responder() {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        let chain = chain();
+        let (_, leaf) = parse_x509_der(&chain[0]).unwrap();
+        let (_, issuer) = parse_x509_der(&chain[1]).unwrap();
+        let serial = leaf.tbs_certificate.raw_serial().to_vec();
+
+        let response = response(&[(&serial, 0xa1)]);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let responder = std::thread::spawn(move || {
+            let (mut sock, _) = listener.accept().unwrap();
+            let mut head = Vec::new();
+            let mut byte = [0u8; 1];
+            while !head.ends_with(b"\r\n\r\n") {
+                sock.read_exact(&mut byte).unwrap();
+                head.push(byte[0]);
+            }
+            let head = String::from_utf8(head).unwrap();
+            let len: usize = head
+                .lines()
+                .find_map(|line| line.strip_prefix("Content-Length: "))
+                .unwrap()
+                .parse()
+                .unwrap();
+            let mut request = vec![0u8; len];
+            sock.read_exact(&mut request).unwrap();
+
+            sock.write_all(
+                format!(
+                    "HTTP/1.0 200 OK\r\nContent-Type: application/ocsp-response\r\n\
+                     Content-Length: {}\r\n\r\n",
+                    response.len()
+                )
+                .as_bytes(),
+            )
+            .unwrap();
+            sock.write_all(&response).unwrap();
+            (head, request)
+        });
+
+        let url = format!("http://127.0.0.1:{}/ocsp", port);
+        assert_eq!(
+            query_responder(&url, &leaf, &issuer, None),
+            Ok(OcspStatus::Revoked)
+        );
+
+        let (head, request) = responder.join().unwrap();
+        assert!(head.starts_with("POST /ocsp HTTP/1.0\r\n"));
+        assert!(head.contains("Content-Type: application/ocsp-request\r\n"));
+        // the CertID identifies the leaf by its issuer key hash and serial number
+        let key_hash = digest::digest(
+            &digest::SHA1_FOR_LEGACY_USE_ONLY,
+            issuer.tbs_certificate.subject_pki.subject_public_key.data,
+        );
+        let cert_id = (0..5).fold(&request[..], |inner, _| expect(SEQUENCE, inner).unwrap().0);
+        let (_, rest) = expect(SEQUENCE, cert_id).unwrap();
+        let (_, rest) = expect(OCTET_STRING, rest).unwrap();
+        let (actual_key_hash, rest) = expect(OCTET_STRING, rest).unwrap();
+        assert_eq!(actual_key_hash, key_hash.as_ref());
+        assert_eq!(expect(INTEGER, rest).unwrap().0, &serial[..]);
+    }
+
+    #[cfg(feature = "revocation")]
+    #[test]
+    fn test_post_too_large() {
+        use std::io::Write;
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let responder = std::thread::spawn(move || {
+            let (mut sock, _) = listener.accept().unwrap();
+            let body = vec![0u8; MAX_OCSP_RESPONSE as usize];
+            // the client hangs up once it has read enough
+            let _ = sock.write_all(b"HTTP/1.0 200 OK\r\n\r\n");
+            let _ = sock.write_all(&body);
+        });
+
+        let url = format!("http://127.0.0.1:{}/ocsp", port);
+        assert_eq!(
+            post(&url, b"request", None),
+            Err("the OCSP response is larger than 65536 bytes".to_string())
+        );
+        responder.join().unwrap();
+    }
+
+    #[cfg(feature = "revocation")]
+    #[test]
+    fn test_query_without_responder() {
+        let chain: Vec<_> = chain().into_iter().map(rustls::Certificate).collect();
+        assert_eq!(
+            query(&chain, None),
+            Err("no HTTP OCSP responder in the certificate".to_string())
+        );
+    }
+}
@@ -0,0 +1,339 @@
+//! Checking many domains concurrently.
+
+use crate::{Cert, CheckSSL, CheckSSLBuilder, CheckSSLError};
+use serde::Serialize;
+use std::io::{Error, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// One line of the JSON Lines output of [`CheckSSL::scan_to_writer`]
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ScanRecord<'a> {
+    Cert {
+        domain: &'a str,
+        #[serde(flatten)]
+        cert: &'a Cert,
+    },
+    Error {
+        domain: &'a str,
+        error: &'a CheckSSLError,
+    },
+}
+
+impl CheckSSL {
+    /// Check many domains concurrently on one worker thread per CPU
+    ///
+    /// A failing domain does not abort the batch, its error is returned at its position.
+    /// The results are in the same order as `domains`.
+    ///
+    /// Example
+    ///
+    /// ```no_run
+    /// use checkssl::CheckSSL;
+    ///
+    /// for (domain, result) in CheckSSL::from_domains(&["rust-lang.org", "crates.io"]) {
+    ///     match result {
+    ///         Ok(certificate) => println!("{}: {}", domain, certificate.server.is_valid),
+    ///         Err(e) => eprintln!("{}: {}", domain, e),
+    ///     }
+    /// }
+    /// ```
+    pub fn from_domains(domains: &[&str]) -> Vec<(String, Result<Cert, CheckSSLError>)> {
+        Self::from_domains_with_workers(domains, default_workers())
+    }
+
+    /// Check many domains concurrently on at most `workers` threads
+    pub fn from_domains_with_workers(
+        domains: &[&str],
+        workers: usize,
+    ) -> Vec<(String, Result<Cert, CheckSSLError>)> {
+        let mut results: Vec<Option<Result<Cert, CheckSSLError>>> =
+            domains.iter().map(|_| None).collect();
+        scan(
+            domains.len(),
+            workers,
+            |index| CheckSSL::from_domain(domains[index]),
+            |index, cert| results[index] = Some(cert),
+        );
+
+        domains
+            .iter()
+            .zip(results)
+            .map(|(domain, cert)| (domain.to_string(), cert.expect("every domain is checked")))
+            .collect()
+    }
+
+    /// Check many domains concurrently, each with its own builder, e.g. to check several ports
+    ///
+    /// The results are in the same order as `checks`.
+    ///
+    /// Example
+    ///
+    /// ```no_run
+    /// use checkssl::{CheckSSL, CheckSSLBuilder};
+    ///
+    /// let checks = [
+    ///     ("example.com", CheckSSLBuilder::new()),
+    ///     ("mail.example.com", CheckSSLBuilder::new().port(993)),
+    /// ];
+    /// for (domain, result) in CheckSSL::from_builders(&checks) {
+    ///     println!("{}: {}", domain, result.is_ok());
+    /// }
+    /// ```
+    pub fn from_builders(
+        checks: &[(&str, CheckSSLBuilder)],
+    ) -> Vec<(String, Result<Cert, CheckSSLError>)> {
+        let mut results: Vec<Option<Result<Cert, CheckSSLError>>> =
+            checks.iter().map(|_| None).collect();
+        Self::scan_builders(checks, |index, cert| results[index] = Some(cert));
+
+        checks
+            .iter()
+            .zip(results)
+            .map(|((domain, _), cert)| (domain.to_string(), cert.expect("every domain is checked")))
+            .collect()
+    }
+
+    /// Check many domains concurrently like [`CheckSSL::from_builders`], handing each result to
+    /// `on_result` with its index in `checks` as soon as it completes
+    ///
+    /// Example
+    ///
+    /// ```no_run
+    /// use checkssl::{CheckSSL, CheckSSLBuilder};
+    ///
+    /// let checks = [
+    ///     ("example.com", CheckSSLBuilder::new()),
+    ///     ("mail.example.com", CheckSSLBuilder::new().port(993)),
+    /// ];
+    /// CheckSSL::scan_builders(&checks, |index, result| {
+    ///     println!("{}: {}", checks[index].0, result.is_ok());
+    /// });
+    /// ```
+    pub fn scan_builders<F>(checks: &[(&str, CheckSSLBuilder)], on_result: F)
+    where
+        F: FnMut(usize, Result<Cert, CheckSSLError>),
+    {
+        scan(
+            checks.len(),
+            default_workers(),
+            |index| {
+                let (domain, builder) = &checks[index];
+                builder.check(domain)
+            },
+            on_result,
+        );
+    }
+
+    /// Check `domain` on each of `ports` concurrently, e.g. to find which ones serve TLS
+    ///
+    /// A failing port does not abort the scan, its error is returned at its position.
+    /// The results are in the same order as `ports`.
+    ///
+    /// Example
+    ///
+    /// ```no_run
+    /// use checkssl::CheckSSL;
+    ///
+    /// for (port, result) in CheckSSL::from_host_ports("example.com", &[443, 8443]) {
+    ///     match result {
+    ///         Ok(certificate) => println!("{}: {}", port, certificate.server.common_name),
+    ///         Err(e) => eprintln!("{}: {}", port, e),
+    ///     }
+    /// }
+    /// ```
+    pub fn from_host_ports(domain: &str, ports: &[u16]) -> Vec<(u16, Result<Cert, CheckSSLError>)> {
+        let mut results: Vec<Option<Result<Cert, CheckSSLError>>> =
+            ports.iter().map(|_| None).collect();
+        scan(
+            ports.len(),
+            default_workers(),
+            |index| CheckSSL::from_domain_with_port(domain, ports[index]),
+            |index, cert| results[index] = Some(cert),
+        );
+
+        ports
+            .iter()
+            .zip(results)
+            .map(|(port, cert)| (*port, cert.expect("every port is checked")))
+            .collect()
+    }
+
+    /// Check many domains concurrently and write one JSON object per line as each check completes
+    ///
+    /// Successful checks are written as the serialized `Cert` with an extra `domain` field,
+    /// failures as `{"domain": "...", "error": {"kind": "...", "message": "..."}}`. Lines are
+    /// written in completion order.
+    ///
+    /// Example
+    ///
+    /// ```no_run
+    /// use checkssl::CheckSSL;
+    ///
+    /// let stdout = std::io::stdout();
+    /// CheckSSL::scan_to_writer(&["rust-lang.org", "crates.io"], stdout.lock()).unwrap();
+    /// ```
+    pub fn scan_to_writer<W: Write>(domains: &[&str], mut writer: W) -> Result<(), Error> {
+        let mut result = Ok(());
+        let check = |index: usize| CheckSSL::from_domain(domains[index]);
+        scan(domains.len(), default_workers(), check, |index, cert| {
+            if result.is_err() {
+                return;
+            }
+            let domain = domains[index];
+            let record = match &cert {
+                Ok(cert) => ScanRecord::Cert { domain, cert },
+                Err(error) => ScanRecord::Error { domain, error },
+            };
+            result = serde_json::to_writer(&mut writer, &record)
+                .map_err(Error::from)
+                .and_then(|_| writer.write_all(b"\n"))
+                .and_then(|_| writer.flush());
+        });
+        result
+    }
+}
+
+fn default_workers() -> usize {
+    thread::available_parallelism()
+        .map(|workers| workers.get())
+        .unwrap_or(1)
+}
+
+/// Run `check` for the indexes below `len` on at most `workers` threads, handing each result
+/// and its index to `on_result` as it completes
+fn scan<C, F>(len: usize, workers: usize, check: C, mut on_result: F)
+where
+    C: Fn(usize) -> Result<Cert, CheckSSLError> + Sync,
+    F: FnMut(usize, Result<Cert, CheckSSLError>),
+{
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..workers.clamp(1, len.max(1)) {
+            let sender = sender.clone();
+            let next = &next;
+            let check = &check;
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= len {
+                    break;
+                }
+                if sender.send((index, check(index))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for (index, cert) in receiver {
+            on_result(index, cert);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_from_domains_keeps_input_order() {
+        let domains = ["c.invalid", "", "a.invalid", "b.invalid"];
+        let results = CheckSSL::from_domains_with_workers(&domains, 2);
+
+        let actual: Vec<&str> = results.iter().map(|(domain, _)| domain.as_str()).collect();
+        assert_eq!(actual, domains);
+        assert!(matches!(
+            results[1].1,
+            Err(CheckSSLError::InvalidServerName)
+        ));
+        assert!(results.iter().all(|(_, result)| result.is_err()));
+    }
+
+    #[test]
+    fn test_from_builders_keeps_input_order() {
+        let checks = [
+            ("b.invalid", CheckSSLBuilder::new().port(8443)),
+            ("", CheckSSLBuilder::new()),
+            ("a.invalid", CheckSSLBuilder::new().port(993)),
+        ];
+        let results = CheckSSL::from_builders(&checks);
+
+        let actual: Vec<&str> = results.iter().map(|(domain, _)| domain.as_str()).collect();
+        assert_eq!(actual, vec!["b.invalid", "", "a.invalid"]);
+        assert!(matches!(
+            results[1].1,
+            Err(CheckSSLError::InvalidServerName)
+        ));
+        assert!(results.iter().all(|(_, result)| result.is_err()));
+    }
+
+    #[test]
+    fn test_scan_builders() {
+        let (open, server) = test_server::serve(test_server::config());
+        let checks = [
+            ("", CheckSSLBuilder::new()),
+            ("127.0.0.1", CheckSSLBuilder::new().port(open)),
+        ];
+
+        let mut results = Vec::new();
+        CheckSSL::scan_builders(&checks, |index, result| results.push((index, result)));
+        server.join().unwrap();
+
+        results.sort_by_key(|(index, _)| *index);
+        assert!(matches!(
+            results[0],
+            (0, Err(CheckSSLError::InvalidServerName))
+        ));
+        assert_eq!(
+            results[1].1.as_ref().unwrap().server.common_name,
+            "localhost"
+        );
+    }
+
+    #[test]
+    fn test_from_host_ports() {
+        let (open, server) = test_server::serve(test_server::config());
+        let closed = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let results = CheckSSL::from_host_ports("127.0.0.1", &[closed, open]);
+        server.join().unwrap();
+
+        let ports: Vec<u16> = results.iter().map(|(port, _)| *port).collect();
+        assert_eq!(ports, vec![closed, open]);
+        assert!(matches!(results[0].1, Err(CheckSSLError::Connect(_))));
+        assert_eq!(
+            results[1].1.as_ref().unwrap().server.common_name,
+            "localhost"
+        );
+    }
+
+    #[test]
+    fn test_scan_to_writer_reports_errors_per_domain() {
+        let mut output = Vec::new();
+        CheckSSL::scan_to_writer(&["a.invalid", "b.invalid"], &mut output).unwrap();
+
+        let mut domains: Vec<String> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let record: serde_json::Value = serde_json::from_str(line).unwrap();
+                assert!(record["error"]["kind"].is_string());
+                assert!(record["error"]["message"].is_string());
+                record["domain"].as_str().unwrap().to_string()
+            })
+            .collect();
+        domains.sort();
+
+        assert_eq!(domains, vec!["a.invalid", "b.invalid"]);
+    }
+}
@@ -0,0 +1,574 @@
+//! Plaintext negotiations performed before a connection is upgraded to TLS.
+
+use crate::{tlv, CheckSSLError};
+use std::io::{Read, Write};
+
+/// LDAPMessage carrying an ExtendedRequest for the StartTLS OID `1.3.6.1.4.1.1466.20037` (RFC 4511)
+const LDAP_STARTTLS_REQUEST: &[u8] = b"\x30\x1d\x02\x01\x01\x77\x18\x80\x161.3.6.1.4.1.1466.20037";
+
+const BER_SEQUENCE: u8 = 0x30;
+const BER_INTEGER: u8 = 0x02;
+const BER_ENUMERATED: u8 = 0x0a;
+const LDAP_EXTENDED_RESPONSE: u8 = 0x78;
+
+/// Longest LDAP response accepted, a StartTLS ExtendedResponse is a few dozen bytes
+const MAX_LDAP_RESPONSE: usize = 64 * 1024;
+
+/// Longest line accepted from a line based protocol, far above what servers send
+const MAX_LINE: usize = 16 * 1024;
+
+/// Longest XMPP tag accepted before the stream is considered garbage
+const MAX_XMPP_TAG: usize = 16 * 1024;
+
+/// Send the LDAP StartTLS extended request and wait for a successful response
+pub(crate) fn ldap<S: Read + Write>(stream: &mut S) -> Result<(), CheckSSLError> {
+    stream.write_all(LDAP_STARTTLS_REQUEST)?;
+    stream.flush()?;
+
+    let message = read_ber_sequence(stream)?;
+    let (tag, message_id, rest) = split_tlv(&message)?;
+    if tag != BER_INTEGER || message_id != [0x01] {
+        return Err(invalid_response(
+            "LDAP response does not match the StartTLS request",
+        ));
+    }
+
+    let (tag, response, _) = split_tlv(rest)?;
+    if tag != LDAP_EXTENDED_RESPONSE {
+        return Err(invalid_response(
+            "LDAP response is not an extended response",
+        ));
+    }
+
+    match split_tlv(response)? {
+        (BER_ENUMERATED, [0x00], _) => Ok(()),
+        (BER_ENUMERATED, code, _) => Err(CheckSSLError::StartTls(format!(
+            "LDAP StartTLS refused with result code {:?}",
+            code
+        ))),
+        _ => Err(invalid_response(
+            "LDAP extended response has no result code",
+        )),
+    }
+}
+
+/// Read the FTP greeting, request `AUTH TLS` and wait for the `234` go-ahead (RFC 4217)
+pub(crate) fn ftp<S: Read + Write>(stream: &mut S) -> Result<(), CheckSSLError> {
+    let (mut code, mut text) = read_reply(stream)?;
+    // 120 means the service will be ready later, the real greeting follows
+    while code.starts_with('1') {
+        let (next_code, next_text) = read_reply(stream)?;
+        code = next_code;
+        text = next_text;
+    }
+    if code != "220" {
+        return Err(invalid_response(&format!(
+            "unexpected FTP greeting: {}",
+            text
+        )));
+    }
+
+    stream.write_all(b"AUTH TLS\r\n")?;
+    stream.flush()?;
+
+    let (code, text) = read_reply(stream)?;
+    if code != "234" {
+        return Err(CheckSSLError::StartTls(format!(
+            "FTP server refused AUTH TLS: {}",
+            text
+        )));
+    }
+    Ok(())
+}
+
+/// Read the SMTP greeting, introduce ourselves with `EHLO` and request `STARTTLS` (RFC 3207)
+pub(crate) fn smtp<S: Read + Write>(stream: &mut S) -> Result<(), CheckSSLError> {
+    let (code, text) = read_reply(stream)?;
+    if code != "220" {
+        return Err(invalid_response(&format!(
+            "unexpected SMTP greeting: {}",
+            text
+        )));
+    }
+
+    stream.write_all(b"EHLO checkssl\r\n")?;
+    stream.flush()?;
+
+    let (code, text) = read_reply(stream)?;
+    if code != "250" {
+        return Err(CheckSSLError::StartTls(format!(
+            "SMTP server refused EHLO: {}",
+            text
+        )));
+    }
+
+    stream.write_all(b"STARTTLS\r\n")?;
+    stream.flush()?;
+
+    let (code, text) = read_reply(stream)?;
+    if code != "220" {
+        return Err(CheckSSLError::StartTls(format!(
+            "SMTP server refused STARTTLS: {}",
+            text
+        )));
+    }
+    Ok(())
+}
+
+/// Read the untagged IMAP greeting, send `A001 STARTTLS` and wait for the tagged `OK` (RFC 3501)
+pub(crate) fn imap<S: Read + Write>(stream: &mut S) -> Result<(), CheckSSLError> {
+    let greeting = read_line(stream)?;
+    if !greeting.starts_with("* OK") {
+        return Err(invalid_response(&format!(
+            "unexpected IMAP greeting: {}",
+            greeting
+        )));
+    }
+
+    stream.write_all(b"A001 STARTTLS\r\n")?;
+    stream.flush()?;
+
+    // untagged responses, e.g. a CAPABILITY update, may come before the tagged completion
+    let mut line = read_line(stream)?;
+    while line.starts_with("* ") {
+        line = read_line(stream)?;
+    }
+    match line.strip_prefix("A001 ") {
+        Some(status) if status.starts_with("OK") => Ok(()),
+        Some(_) => Err(CheckSSLError::StartTls(format!(
+            "IMAP server refused STARTTLS: {}",
+            line
+        ))),
+        None => Err(invalid_response(&format!(
+            "unexpected IMAP response: {}",
+            line
+        ))),
+    }
+}
+
+/// Read the POP3 greeting, send `STLS` and wait for `+OK` (RFC 2595)
+pub(crate) fn pop3<S: Read + Write>(stream: &mut S) -> Result<(), CheckSSLError> {
+    let greeting = read_line(stream)?;
+    if !greeting.starts_with("+OK") {
+        return Err(invalid_response(&format!(
+            "unexpected POP3 greeting: {}",
+            greeting
+        )));
+    }
+
+    stream.write_all(b"STLS\r\n")?;
+    stream.flush()?;
+
+    let line = read_line(stream)?;
+    if !line.starts_with("+OK") {
+        return Err(CheckSSLError::StartTls(format!(
+            "POP3 server refused STLS: {}",
+            line
+        )));
+    }
+    Ok(())
+}
+
+/// Open an XMPP stream to `domain`, wait for `<starttls>` among the features and for `<proceed/>` (RFC 6120)
+///
+/// `server_mode` opens a server-to-server stream (`jabber:server`, port 5269) instead of a
+/// client one (`jabber:client`, port 5222).
+pub(crate) fn xmpp<S: Read + Write>(
+    stream: &mut S,
+    domain: &str,
+    server_mode: bool,
+) -> Result<(), CheckSSLError> {
+    let namespace = if server_mode {
+        "jabber:server"
+    } else {
+        "jabber:client"
+    };
+    write!(
+        stream,
+        "<?xml version='1.0'?><stream:stream to='{}' xmlns='{}' \
+         xmlns:stream='http://etherx.jabber.org/streams' version='1.0'>",
+        domain, namespace
+    )?;
+    stream.flush()?;
+
+    let mut offered = false;
+    loop {
+        let tag = read_tag(stream)?;
+        match local_name(&tag) {
+            "error" => {
+                return Err(CheckSSLError::StartTls(format!(
+                    "XMPP server closed the stream: {}",
+                    tag
+                )))
+            }
+            "starttls" => offered = true,
+            // `<stream:features/>` advertises nothing
+            "features" if tag.starts_with("</") || tag.ends_with("/>") => break,
+            _ => {}
+        }
+    }
+    if !offered {
+        return Err(CheckSSLError::StartTls(
+            "XMPP server does not offer STARTTLS".to_string(),
+        ));
+    }
+
+    stream.write_all(b"<starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>")?;
+    stream.flush()?;
+
+    let tag = read_tag(stream)?;
+    match local_name(&tag) {
+        "proceed" => Ok(()),
+        "failure" => Err(CheckSSLError::StartTls(
+            "XMPP server refused STARTTLS".to_string(),
+        )),
+        _ => Err(invalid_response(&format!(
+            "unexpected XMPP response: {}",
+            tag
+        ))),
+    }
+}
+
+/// Read up to the next `<...>` tag, skipping the character data before it
+///
+/// Like `read_line`, stops right after the closing `>` so the TLS handshake stays in the socket.
+fn read_tag<S: Read>(stream: &mut S) -> Result<String, CheckSSLError> {
+    let mut byte = [0u8; 1];
+    loop {
+        stream.read_exact(&mut byte)?;
+        if byte[0] == b'<' {
+            break;
+        }
+    }
+    let mut tag = vec![b'<'];
+    while byte[0] != b'>' {
+        if tag.len() > MAX_XMPP_TAG {
+            return Err(invalid_response("XMPP tag is too long"));
+        }
+        stream.read_exact(&mut byte)?;
+        tag.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&tag).into_owned())
+}
+
+/// Element name of a tag without its namespace prefix, `<stream:features>` gives `features`
+fn local_name(tag: &str) -> &str {
+    let name = tag
+        .trim_start_matches('<')
+        .trim_start_matches('/')
+        .split(|c: char| c.is_whitespace() || c == '/' || c == '>')
+        .next()
+        .unwrap_or("");
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+/// Read a possibly multi-line FTP or SMTP reply (`220-...` continued until `220 ...`) as `(code, last line)`
+fn read_reply<S: Read>(stream: &mut S) -> Result<(String, String), CheckSSLError> {
+    let line = read_line(stream)?;
+    if line.len() < 3 || !line.as_bytes()[..3].iter().all(u8::is_ascii_digit) {
+        return Err(invalid_response(&format!("malformed reply: {}", line)));
+    }
+    let code = line[..3].to_string();
+
+    let mut last = line;
+    if last.as_bytes().get(3) == Some(&b'-') {
+        let terminator = format!("{} ", code);
+        while !last.starts_with(&terminator) && last != code {
+            last = read_line(stream)?;
+        }
+    }
+    Ok((code, last))
+}
+
+/// Read a single CRLF (or LF) terminated line without consuming anything past it
+///
+/// Reads one byte at a time on purpose: the bytes following the last plaintext line
+/// belong to the TLS handshake and must stay in the socket.
+pub(crate) fn read_line<S: Read>(stream: &mut S) -> Result<String, CheckSSLError> {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    loop {
+        stream.read_exact(&mut byte)?;
+        if byte[0] == b'\n' {
+            break;
+        }
+        if line.len() >= MAX_LINE {
+            return Err(invalid_response("line is too long"));
+        }
+        line.push(byte[0]);
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Ok(String::from_utf8_lossy(&line).into_owned())
+}
+
+/// Read a whole BER encoded SEQUENCE from the stream and return its content
+fn read_ber_sequence<S: Read>(stream: &mut S) -> Result<Vec<u8>, CheckSSLError> {
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header)?;
+    if header[0] != BER_SEQUENCE {
+        return Err(invalid_response("expected a BER sequence"));
+    }
+
+    let len = if header[1] & 0x80 == 0 {
+        usize::from(header[1])
+    } else {
+        let mut len_bytes = vec![0u8; usize::from(header[1] & 0x7f)];
+        if len_bytes.len() > 4 {
+            return Err(invalid_response("BER length is too large"));
+        }
+        stream.read_exact(&mut len_bytes)?;
+        len_bytes
+            .iter()
+            .fold(0usize, |len, byte| (len << 8) | usize::from(*byte))
+    };
+    // checked before allocating, the length comes from the server
+    if len > MAX_LDAP_RESPONSE {
+        return Err(invalid_response("BER length is too large"));
+    }
+
+    let mut content = vec![0u8; len];
+    stream.read_exact(&mut content)?;
+    Ok(content)
+}
+
+/// Split the first BER element off a buffer as `(tag, value, rest)`
+fn split_tlv(input: &[u8]) -> Result<(u8, &[u8], &[u8]), CheckSSLError> {
+    tlv::split_tlv(input).ok_or_else(|| invalid_response("truncated BER element"))
+}
+
+fn invalid_response(message: &str) -> CheckSSLError {
+    CheckSSLError::StartTls(message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// In-memory peer that replays a canned server script and records what the client sent
+    struct MockStream {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl MockStream {
+        fn new(input: &[u8]) -> MockStream {
+            MockStream {
+                input: Cursor::new(input.to_vec()),
+                output: Vec::new(),
+            }
+        }
+    }
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_ftp_auth_tls() {
+        let mut stream = MockStream::new(
+            b"220-Welcome\r\n220-to the server\r\n220 Ready\r\n234 AUTH TLS OK.\r\n\x16\x03",
+        );
+
+        ftp(&mut stream).unwrap();
+        assert_eq!(stream.output, b"AUTH TLS\r\n");
+
+        let mut remaining = Vec::new();
+        stream.read_to_end(&mut remaining).unwrap();
+        assert_eq!(remaining, b"\x16\x03");
+    }
+
+    #[test]
+    fn test_ftp_auth_tls_refused() {
+        let mut stream = MockStream::new(b"220 Ready\r\n502 Command not implemented\r\n");
+
+        let actual = ftp(&mut stream);
+        assert!(matches!(actual, Err(CheckSSLError::StartTls(_))));
+    }
+
+    #[test]
+    fn test_read_line_too_long() {
+        let mut stream = MockStream::new(&[b'2'; MAX_LINE + 1]);
+
+        let actual = read_line(&mut stream);
+        assert!(
+            matches!(actual, Err(CheckSSLError::StartTls(message)) if message == "line is too long")
+        );
+    }
+
+    #[test]
+    fn test_smtp_starttls() {
+        let mut stream = MockStream::new(
+            b"220 mail.example.com ESMTP\r\n250-mail.example.com\r\n250-PIPELINING\r\n\
+              250 STARTTLS\r\n220 2.0.0 Ready to start TLS\r\n\x16\x03",
+        );
+
+        smtp(&mut stream).unwrap();
+        assert_eq!(stream.output, b"EHLO checkssl\r\nSTARTTLS\r\n");
+
+        let mut remaining = Vec::new();
+        stream.read_to_end(&mut remaining).unwrap();
+        assert_eq!(remaining, b"\x16\x03");
+    }
+
+    #[test]
+    fn test_smtp_starttls_refused() {
+        let mut stream = MockStream::new(
+            b"220 mail.example.com ESMTP\r\n250 mail.example.com\r\n454 TLS not available\r\n",
+        );
+
+        let actual = smtp(&mut stream);
+        assert!(matches!(actual, Err(CheckSSLError::StartTls(_))));
+    }
+
+    #[test]
+    fn test_imap_starttls() {
+        let mut stream = MockStream::new(
+            b"* OK [CAPABILITY IMAP4rev1 STARTTLS] ready\r\n* CAPABILITY IMAP4rev1\r\n\
+              A001 OK Begin TLS negotiation now\r\n\x16\x03",
+        );
+
+        imap(&mut stream).unwrap();
+        assert_eq!(stream.output, b"A001 STARTTLS\r\n");
+
+        let mut remaining = Vec::new();
+        stream.read_to_end(&mut remaining).unwrap();
+        assert_eq!(remaining, b"\x16\x03");
+    }
+
+    #[test]
+    fn test_imap_starttls_refused() {
+        let mut stream = MockStream::new(b"* OK ready\r\nA001 BAD unknown command\r\n");
+
+        let actual = imap(&mut stream);
+        assert!(matches!(actual, Err(CheckSSLError::StartTls(_))));
+    }
+
+    #[test]
+    fn test_pop3_stls() {
+        let mut stream = MockStream::new(b"+OK POP3 ready\r\n+OK Begin TLS\r\n\x16\x03");
+
+        pop3(&mut stream).unwrap();
+        assert_eq!(stream.output, b"STLS\r\n");
+
+        let mut remaining = Vec::new();
+        stream.read_to_end(&mut remaining).unwrap();
+        assert_eq!(remaining, b"\x16\x03");
+    }
+
+    #[test]
+    fn test_pop3_stls_refused() {
+        let mut stream = MockStream::new(b"+OK POP3 ready\r\n-ERR command not supported\r\n");
+
+        let actual = pop3(&mut stream);
+        assert!(matches!(actual, Err(CheckSSLError::StartTls(_))));
+    }
+
+    #[test]
+    fn test_xmpp_starttls() {
+        let mut stream = MockStream::new(
+            b"<?xml version='1.0'?><stream:stream from='example.com' id='1' version='1.0' \
+              xmlns='jabber:client' xmlns:stream='http://etherx.jabber.org/streams'>\
+              <stream:features><starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'><required/></starttls>\
+              <mechanisms xmlns='urn:ietf:params:xml:ns:xmpp-sasl'><mechanism>PLAIN</mechanism></mechanisms>\
+              </stream:features><proceed xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>\x16\x03",
+        );
+
+        xmpp(&mut stream, "example.com", false).unwrap();
+        let output = String::from_utf8(stream.output.clone()).unwrap();
+        assert!(output.contains("<stream:stream to='example.com' xmlns='jabber:client'"));
+        assert!(output.ends_with("<starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>"));
+
+        let mut remaining = Vec::new();
+        stream.read_to_end(&mut remaining).unwrap();
+        assert_eq!(remaining, b"\x16\x03");
+    }
+
+    #[test]
+    fn test_xmpp_starttls_server_mode() {
+        let mut stream = MockStream::new(
+            b"<stream:stream xmlns='jabber:server'><stream:features>\
+              <starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'/></stream:features><proceed/>",
+        );
+
+        xmpp(&mut stream, "example.com", true).unwrap();
+        let output = String::from_utf8(stream.output).unwrap();
+        assert!(output.contains("xmlns='jabber:server'"));
+    }
+
+    #[test]
+    fn test_xmpp_starttls_not_offered() {
+        let mut stream =
+            MockStream::new(b"<stream:stream><stream:features><mechanisms/></stream:features>");
+
+        let actual = xmpp(&mut stream, "example.com", false);
+        assert!(matches!(actual, Err(CheckSSLError::StartTls(_))));
+        // the client gives up without requesting STARTTLS
+        assert!(!String::from_utf8(stream.output)
+            .unwrap()
+            .contains("<starttls"));
+    }
+
+    #[test]
+    fn test_xmpp_starttls_refused() {
+        let mut stream = MockStream::new(
+            b"<stream:stream><stream:features><starttls/></stream:features>\
+              <failure xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>",
+        );
+
+        let actual = xmpp(&mut stream, "example.com", false);
+        assert!(matches!(actual, Err(CheckSSLError::StartTls(_))));
+    }
+
+    #[test]
+    fn test_ldap_starttls_success() {
+        let mut stream =
+            MockStream::new(b"\x30\x0c\x02\x01\x01\x78\x07\x0a\x01\x00\x04\x00\x04\x00");
+
+        ldap(&mut stream).unwrap();
+        assert_eq!(stream.output, LDAP_STARTTLS_REQUEST);
+    }
+
+    #[test]
+    fn test_ldap_starttls_refused() {
+        let mut stream =
+            MockStream::new(b"\x30\x0c\x02\x01\x01\x78\x07\x0a\x01\x02\x04\x00\x04\x00");
+
+        let actual = ldap(&mut stream);
+        assert!(matches!(actual, Err(CheckSSLError::StartTls(_))));
+    }
+
+    #[test]
+    fn test_ldap_starttls_too_large() {
+        let mut stream = MockStream::new(b"\x30\x84\xff\xff\xff\xff\x02\x01\x01");
+
+        let actual = ldap(&mut stream);
+        assert!(
+            matches!(actual, Err(CheckSSLError::StartTls(message)) if message == "BER length is too large")
+        );
+    }
+
+    #[test]
+    fn test_ldap_starttls_truncated() {
+        let mut stream = MockStream::new(b"\x30\x0c\x02\x01\x01\x78");
+
+        let actual = ldap(&mut stream);
+        assert!(
+            matches!(actual, Err(CheckSSLError::Connect(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof)
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! Local TLS server for the tests that need a real handshake.
+
+use rustls::{Certificate, PrivateKey, ServerConfig, ServerConnection};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// Serve the `localhost` / `127.0.0.1` fixture certificate
+pub(crate) fn config() -> ServerConfig {
+    config_with_ocsp(Vec::new())
+}
+
+/// Serve the fixture certificate, stapling `ocsp` unless it is empty
+pub(crate) fn config_with_ocsp(ocsp: Vec<u8>) -> ServerConfig {
+    ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert_with_ocsp_and_sct(
+            vec![certificate()],
+            PrivateKey(include_bytes!("../tests/fixtures/server.key.der").to_vec()),
+            ocsp,
+            Vec::new(),
+        )
+        .unwrap()
+}
+
+pub(crate) fn certificate() -> Certificate {
+    Certificate(include_bytes!("../tests/fixtures/server.der").to_vec())
+}
+
+/// Accept a single connection on a free port of 127.0.0.1
+///
+/// The handle gives back the server side of the connection once the client hung up.
+pub(crate) fn serve(config: ServerConfig) -> (u16, JoinHandle<ServerConnection>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let handle = thread::spawn(move || accept(&listener, config));
+    (port, handle)
+}
+
+/// Accept the next connection on `listener` and serve it until the client hangs up
+pub(crate) fn accept(listener: &TcpListener, config: ServerConfig) -> ServerConnection {
+    accept_request(listener, config).0
+}
+
+/// Like [`accept`], also giving back the application data the client sent
+pub(crate) fn accept_request(
+    listener: &TcpListener,
+    config: ServerConfig,
+) -> (ServerConnection, Vec<u8>) {
+    let (mut sock, _) = listener.accept().unwrap();
+    let mut conn = ServerConnection::new(Arc::new(config)).unwrap();
+    // drain until the client hangs up, so it never sees a reset
+    let mut request = Vec::new();
+    let _ = rustls::Stream::new(&mut conn, &mut sock).read_to_end(&mut request);
+    (conn, request)
+}
+
+/// Accept a single connection on a free port of 127.0.0.1 and answer its request with `response`
+pub(crate) fn serve_response(config: ServerConfig, response: Vec<u8>) -> (u16, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let handle = thread::spawn(move || accept_response(&listener, config, &response));
+    (port, handle)
+}
+
+/// Accept the next connection on `listener` and answer its request with `response`
+pub(crate) fn accept_response(listener: &TcpListener, config: ServerConfig, response: &[u8]) {
+    let (mut sock, _) = listener.accept().unwrap();
+    let mut conn = ServerConnection::new(Arc::new(config)).unwrap();
+    let mut stream = rustls::Stream::new(&mut conn, &mut sock);
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        match stream.read(&mut buf) {
+            Ok(0) | Err(_) => return,
+            Ok(len) => request.extend_from_slice(&buf[..len]),
+        }
+    }
+    stream.write_all(response).unwrap();
+    let _ = stream.read_to_end(&mut request);
+}
@@ -0,0 +1,280 @@
+//! Rendering of a chain in the layout of `openssl x509 -text`.
+
+use crate::{Cert, IntermediateCert, ServerCert};
+use chrono::{DateTime, Utc};
+use std::fmt::{self, Write};
+
+/// The fields shared by the server and intermediate certificates
+struct Certificate<'a> {
+    version: u32,
+    serial_number: &'a str,
+    signature_algorithm: &'a str,
+    issuer_dn: &'a str,
+    not_before: DateTime<Utc>,
+    not_after: DateTime<Utc>,
+    subject_dn: &'a str,
+    public_key_algorithm: &'a str,
+    public_key_bits: usize,
+    critical_extensions: &'a [String],
+    subject_key_id: &'a str,
+    authority_key_id: &'a str,
+}
+
+impl<'a> From<&'a ServerCert> for Certificate<'a> {
+    fn from(cert: &'a ServerCert) -> Self {
+        Certificate {
+            version: cert.version,
+            serial_number: &cert.serial_number,
+            signature_algorithm: &cert.signature_algorithm,
+            issuer_dn: &cert.issuer_dn,
+            not_before: cert.not_before,
+            not_after: cert.not_after,
+            subject_dn: &cert.subject_dn,
+            public_key_algorithm: &cert.public_key_algorithm,
+            public_key_bits: cert.public_key_bits,
+            critical_extensions: &cert.critical_extensions,
+            subject_key_id: &cert.subject_key_id,
+            authority_key_id: &cert.authority_key_id,
+        }
+    }
+}
+
+impl<'a> From<&'a IntermediateCert> for Certificate<'a> {
+    fn from(cert: &'a IntermediateCert) -> Self {
+        Certificate {
+            version: cert.version,
+            serial_number: &cert.serial_number,
+            signature_algorithm: &cert.signature_algorithm,
+            issuer_dn: &cert.issuer_dn,
+            not_before: cert.not_before,
+            not_after: cert.not_after,
+            subject_dn: &cert.subject_dn,
+            public_key_algorithm: &cert.public_key_algorithm,
+            public_key_bits: cert.public_key_bits,
+            critical_extensions: &cert.critical_extensions,
+            subject_key_id: &cert.subject_key_id,
+            authority_key_id: &cert.authority_key_id,
+        }
+    }
+}
+
+impl Cert {
+    /// The server certificate then every intermediate, laid out like `openssl x509 -text`
+    ///
+    /// Only the parsed fields are rendered, names in RFC 4514 form.
+    ///
+    /// Example
+    ///
+    /// ```no_run
+    /// use checkssl::CheckSSL;
+    ///
+    /// let certificate = CheckSSL::from_domain("rust-lang.org").unwrap();
+    /// print!("{}", certificate.to_openssl_text());
+    /// ```
+    pub fn to_openssl_text(&self) -> String {
+        let mut text = String::new();
+        let server = &self.server;
+        let mut extensions = Vec::new();
+        if !server.key_usage.is_empty() {
+            let usages: Vec<&str> = server
+                .key_usage
+                .iter()
+                .map(String::as_str)
+                .map(key_usage)
+                .collect();
+            extensions.push(("X509v3 Key Usage", "keyUsage", usages.join(", ")));
+        }
+        if !server.extended_key_usage.is_empty() {
+            let usages: Vec<&str> = server
+                .extended_key_usage
+                .iter()
+                .map(String::as_str)
+                .map(extended_key_usage)
+                .collect();
+            extensions.push((
+                "X509v3 Extended Key Usage",
+                "extendedKeyUsage",
+                usages.join(", "),
+            ));
+        }
+        let names: Vec<String> = server
+            .sans
+            .iter()
+            .map(|name| format!("DNS:{}", name))
+            .chain(
+                server
+                    .san_ip_addresses
+                    .iter()
+                    .map(|ip| format!("IP Address:{}", ip)),
+            )
+            .chain(
+                server
+                    .san_emails
+                    .iter()
+                    .map(|email| format!("email:{}", email)),
+            )
+            .chain(server.san_uris.iter().map(|uri| format!("URI:{}", uri)))
+            .collect();
+        if !names.is_empty() {
+            extensions.push((
+                "X509v3 Subject Alternative Name",
+                "subjectAltName",
+                names.join(", "),
+            ));
+        }
+        // writing to a String never fails
+        write_certificate(&mut text, &server.into(), extensions).expect("formatting failed");
+        for intermediate in &self.intermediates {
+            write_certificate(&mut text, &intermediate.into(), Vec::new())
+                .expect("formatting failed");
+        }
+        text
+    }
+}
+
+/// One `Certificate:` block, with `extensions` as (title, short name, value) after the key identifiers
+fn write_certificate(
+    text: &mut String,
+    cert: &Certificate,
+    mut extensions: Vec<(&str, &str, String)>,
+) -> fmt::Result {
+    let date = |time: DateTime<Utc>| time.format("%b %e %H:%M:%S %Y GMT").to_string();
+
+    writeln!(text, "Certificate:")?;
+    writeln!(text, "    Data:")?;
+    writeln!(
+        text,
+        "        Version: {} (0x{:x})",
+        cert.version,
+        cert.version.saturating_sub(1)
+    )?;
+    writeln!(text, "        Serial Number:")?;
+    writeln!(text, "            {}", cert.serial_number.to_lowercase())?;
+    writeln!(
+        text,
+        "        Signature Algorithm: {}",
+        cert.signature_algorithm
+    )?;
+    writeln!(text, "        Issuer: {}", cert.issuer_dn)?;
+    writeln!(text, "        Validity")?;
+    writeln!(text, "            Not Before: {}", date(cert.not_before))?;
+    writeln!(text, "            Not After : {}", date(cert.not_after))?;
+    writeln!(text, "        Subject: {}", cert.subject_dn)?;
+    writeln!(text, "        Subject Public Key Info:")?;
+    writeln!(
+        text,
+        "            Public Key Algorithm: {}",
+        cert.public_key_algorithm
+    )?;
+    writeln!(
+        text,
+        "                Public-Key: ({} bit)",
+        cert.public_key_bits
+    )?;
+
+    if !cert.authority_key_id.is_empty() {
+        extensions.insert(
+            0,
+            (
+                "X509v3 Authority Key Identifier",
+                "authorityKeyIdentifier",
+                colon_hex(cert.authority_key_id),
+            ),
+        );
+    }
+    if !cert.subject_key_id.is_empty() {
+        extensions.insert(
+            0,
+            (
+                "X509v3 Subject Key Identifier",
+                "subjectKeyIdentifier",
+                colon_hex(cert.subject_key_id),
+            ),
+        );
+    }
+    if extensions.is_empty() {
+        return Ok(());
+    }
+    writeln!(text, "        X509v3 extensions:")?;
+    for (title, name, value) in extensions {
+        let critical = cert.critical_extensions.iter().any(|c| c == name);
+        writeln!(
+            text,
+            "            {}:{}",
+            title,
+            if critical { " critical" } else { "" }
+        )?;
+        writeln!(text, "                {}", value)?;
+    }
+    Ok(())
+}
+
+/// `0a1b...` as `0A:1B:...`
+fn colon_hex(hex: &str) -> String {
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| String::from_utf8_lossy(pair).to_uppercase())
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn key_usage(usage: &str) -> &str {
+    match usage {
+        "digitalSignature" => "Digital Signature",
+        "nonRepudiation" => "Non Repudiation",
+        "keyEncipherment" => "Key Encipherment",
+        "dataEncipherment" => "Data Encipherment",
+        "keyAgreement" => "Key Agreement",
+        "keyCertSign" => "Certificate Sign",
+        "cRLSign" => "CRL Sign",
+        "encipherOnly" => "Encipher Only",
+        "decipherOnly" => "Decipher Only",
+        usage => usage,
+    }
+}
+
+fn extended_key_usage(usage: &str) -> &str {
+    match usage {
+        "anyExtendedKeyUsage" => "Any Extended Key Usage",
+        "serverAuth" => "TLS Web Server Authentication",
+        "clientAuth" => "TLS Web Client Authentication",
+        "codeSigning" => "Code Signing",
+        "emailProtection" => "E-mail Protection",
+        "timeStamping" => "Time Stamping",
+        "OCSPSigning" => "OCSP Signing",
+        usage => usage,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CheckSSL;
+
+    #[test]
+    fn test_to_openssl_text() {
+        let cert = CheckSSL::from_pem(include_bytes!("../tests/fixtures/chain.pem")).unwrap();
+        let text = cert.to_openssl_text();
+
+        assert_eq!(text.matches("Certificate:\n").count(), 2);
+        assert!(text.starts_with("Certificate:\n    Data:\n        Version: 3 (0x2)\n"));
+        for line in [
+            "        Subject: CN=example.com,O=Example Inc,L=San Francisco,ST=California,C=US\n",
+            "        Issuer: CN=Checkssl Test Intermediate,O=Checkssl Test,C=US\n",
+            "            X509v3 Key Usage: critical\n                Digital Signature, Key Encipherment\n",
+            "            X509v3 Extended Key Usage:\n                TLS Web Server Authentication\n",
+            "                DNS:example.com, DNS:*.example.com\n",
+            "            Public Key Algorithm: rsaEncryption\n                Public-Key: (2048 bit)\n",
+        ] {
+            assert!(text.contains(line), "{} not in {}", line, text);
+        }
+        let serial = format!(
+            "        Serial Number:\n            {}\n",
+            cert.server.serial_number.to_lowercase()
+        );
+        assert!(text.contains(&serial));
+        assert!(text.contains(&format!(
+            "            Not After : {}\n",
+            cert.server.not_after.format("%b %e %H:%M:%S %Y GMT")
+        )));
+    }
+}
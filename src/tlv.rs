@@ -0,0 +1,65 @@
+//! Splitting BER / DER encoded elements, for the few structures no parser crate handles for us.
+
+/// Split the first element off a buffer as `(tag, value, rest)`
+///
+/// Only single byte tags are supported. Returns `None` when the element is truncated.
+pub(crate) fn split_tlv(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (tag, input) = input.split_first()?;
+    let (first, input) = input.split_first()?;
+
+    let (len, input) = if first & 0x80 == 0 {
+        (usize::from(*first), input)
+    } else {
+        let count = usize::from(first & 0x7f);
+        if count > 4 || input.len() < count {
+            return None;
+        }
+        let len = input[..count]
+            .iter()
+            .fold(0usize, |len, byte| (len << 8) | usize::from(*byte));
+        (len, &input[count..])
+    };
+
+    if input.len() < len {
+        return None;
+    }
+    Some((*tag, &input[..len], &input[len..]))
+}
+
+/// Encode a single element with a DER length
+#[cfg(any(test, feature = "revocation"))]
+pub(crate) fn encode_tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let len = content.len();
+    let mut element = vec![tag];
+    match len {
+        0..=0x7f => element.push(len as u8),
+        0x80..=0xff => element.extend_from_slice(&[0x81, len as u8]),
+        _ => element.extend_from_slice(&[0x82, (len >> 8) as u8, len as u8]),
+    }
+    element.extend_from_slice(content);
+    element
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_tlv() {
+        assert_eq!(
+            split_tlv(b"\x02\x01\x05\x04\x00"),
+            Some((0x02, &b"\x05"[..], &b"\x04\x00"[..]))
+        );
+        let long = [&[0x04, 0x81, 0x80][..], &[0u8; 0x80]].concat();
+        assert_eq!(split_tlv(&long), Some((0x04, &[0u8; 0x80][..], &[][..])));
+        assert_eq!(split_tlv(b"\x02\x02\x05"), None);
+        assert_eq!(split_tlv(b"\x02"), None);
+    }
+
+    #[test]
+    fn test_encode_tlv() {
+        assert_eq!(encode_tlv(0x02, b"\x05"), b"\x02\x01\x05");
+        let long = encode_tlv(0x04, &[0u8; 0x100]);
+        assert_eq!(split_tlv(&long), Some((0x04, &[0u8; 0x100][..], &[][..])));
+    }
+}
@@ -0,0 +1,147 @@
+#!/bin/sh
+# Regenerate the certificate fixtures used by the unit tests.
+#
+# Certificates are valid for ten years from generation; rerun this script
+# (and commit the result) once they expire.
+set -e
+cd "$(dirname "$0")"
+tmp=$(mktemp -d)
+trap 'rm -rf "$tmp"' EXIT
+
+ca_ext="basicConstraints=critical,CA:TRUE
+keyUsage=critical,keyCertSign,cRLSign
+subjectKeyIdentifier=hash
+authorityKeyIdentifier=keyid"
+
+leaf_ext="basicConstraints=critical,CA:FALSE
+keyUsage=critical,digitalSignature,keyEncipherment
+extendedKeyUsage=serverAuth
+subjectKeyIdentifier=hash
+authorityKeyIdentifier=keyid
+subjectAltName=DNS:example.com,DNS:*.example.com"
+
+for name in root1 root2 intermediate leaf self-signed sans revocation pathlen; do
+    openssl genrsa -out "$tmp/$name.key" 2048 2>/dev/null
+done
+
+for name in root1 root2; do
+    openssl req -x509 -new -key "$tmp/$name.key" -days 3650 -sha256 \
+        -subj "/C=US/O=Checkssl Test/CN=Checkssl Test $name" \
+        -addext "basicConstraints=critical,CA:TRUE" \
+        -addext "keyUsage=critical,keyCertSign,cRLSign" \
+        -out "$tmp/$name.pem"
+done
+
+openssl req -new -key "$tmp/intermediate.key" \
+    -subj "/C=US/O=Checkssl Test/CN=Checkssl Test Intermediate" -out "$tmp/intermediate.csr"
+for root in root1 root2; do
+    openssl x509 -req -in "$tmp/intermediate.csr" -CA "$tmp/$root.pem" -CAkey "$tmp/$root.key" \
+        -CAcreateserial -days 3650 -sha256 -extfile /dev/stdin \
+        -out "$tmp/intermediate-$root.pem" <<EXT
+$ca_ext
+EXT
+done
+
+openssl req -new -key "$tmp/leaf.key" \
+    -subj "/C=US/ST=California/L=San Francisco/O=Example Inc/CN=example.com" -out "$tmp/leaf.csr"
+openssl x509 -req -in "$tmp/leaf.csr" -CA "$tmp/intermediate-root1.pem" -CAkey "$tmp/intermediate.key" \
+    -CAcreateserial -days 3650 -sha256 -extfile /dev/stdin -out "$tmp/leaf.pem" <<EXT
+$leaf_ext
+EXT
+
+openssl req -x509 -new -key "$tmp/self-signed.key" -days 3650 -sha256 \
+    -subj "/CN=self-signed.example.com" \
+    -addext "basicConstraints=critical,CA:FALSE" \
+    -addext "subjectAltName=DNS:self-signed.example.com" \
+    -out "$tmp/self-signed.pem"
+
+openssl req -x509 -new -key "$tmp/sans.key" -days 3650 -sha256 \
+    -subj "/CN=sans.example.com" \
+    -addext "basicConstraints=critical,CA:FALSE" \
+    -addext "subjectAltName=DNS:sans.example.com,IP:192.0.2.1,IP:2001:db8::1,email:admin@example.com,URI:https://sans.example.com/" \
+    -out "$tmp/sans.pem"
+
+openssl req -x509 -new -key "$tmp/revocation.key" -days 3650 -sha256 \
+    -subj "/CN=revocation.example.com" \
+    -addext "basicConstraints=critical,CA:FALSE" \
+    -addext "authorityInfoAccess=OCSP;URI:http://ocsp.example.com,caIssuers;URI:http://ca.example.com/ca.der" \
+    -addext "crlDistributionPoints=URI:http://crl.example.com/ca.crl,URI:ldap://ldap.example.com/cn=ca" \
+    -out "$tmp/revocation.pem"
+
+openssl req -x509 -new -key "$tmp/pathlen.key" -days 3650 -sha256 \
+    -subj "/CN=Checkssl Test pathlen" \
+    -addext "basicConstraints=critical,CA:TRUE,pathlen:1" \
+    -addext "keyUsage=critical,keyCertSign,cRLSign" \
+    -out "$tmp/pathlen.pem"
+
+for name in root1 root2 intermediate-root1 intermediate-root2 leaf self-signed sans revocation pathlen; do
+    openssl x509 -in "$tmp/$name.pem" -outform DER -out "$name.der"
+done
+
+cat "$tmp/leaf.pem" "$tmp/intermediate-root1.pem" > chain.pem
+
+# key and certificate of the local TLS server used by the handshake tests, the key is kept on purpose
+openssl req -x509 -new -newkey ec -pkeyopt ec_paramgen_curve:prime256v1 -nodes \
+    -keyout "$tmp/server.key" -days 3650 -sha256 \
+    -subj "/CN=localhost" \
+    -addext "basicConstraints=critical,CA:FALSE" \
+    -addext "subjectAltName=DNS:localhost,IP:127.0.0.1" \
+    -outform DER -out server.der
+openssl pkcs8 -topk8 -nocrypt -in "$tmp/server.key" -outform DER -out server.key.der
+
+# client certificate and key used by the mutual TLS test, PEM encoded as users would supply them
+openssl req -x509 -new -newkey ec -pkeyopt ec_paramgen_curve:prime256v1 -nodes \
+    -keyout "$tmp/client.key" -days 3650 -sha256 \
+    -subj "/CN=Checkssl Test client" \
+    -addext "basicConstraints=critical,CA:FALSE" \
+    -addext "extendedKeyUsage=clientAuth" \
+    -out client.pem
+openssl pkcs8 -topk8 -nocrypt -in "$tmp/client.key" -out client.key.pem
+
+# root valid until the GeneralizedTime maximum, 9999-12-31 23:59:59, only `openssl ca` sets exact dates
+openssl genrsa -out "$tmp/far-future.key" 2048 2>/dev/null
+touch "$tmp/index.txt"
+echo 01 > "$tmp/serial"
+cat > "$tmp/ca.cnf" <<CNF
+[ca]
+default_ca = ca
+[ca]
+database = $tmp/index.txt
+new_certs_dir = $tmp
+serial = $tmp/serial
+default_md = sha256
+policy = policy
+x509_extensions = ext
+[policy]
+commonName = supplied
+[ext]
+basicConstraints = critical,CA:TRUE
+keyUsage = critical,keyCertSign,cRLSign
+CNF
+openssl req -new -key "$tmp/far-future.key" -subj "/CN=Checkssl Test far future root" -out "$tmp/far-future.csr"
+openssl ca -config "$tmp/ca.cnf" -selfsign -keyfile "$tmp/far-future.key" -in "$tmp/far-future.csr" \
+    -startdate 20240101000000Z -enddate 99991231235959Z -batch -notext -outdir "$tmp" \
+    -out "$tmp/far-future.pem" 2>/dev/null
+openssl x509 -in "$tmp/far-future.pem" -outform DER -out far-future.der
+
+# leaf embedding a list of two made-up SCTs, from the logs 0101...01 and 0202...02
+sct_list=04640062002f0001010101010101010101010101010101010101010101010101010101010101010000018bcfe56800000004030000002f0002020202020202020202020202020202020202020202020202020202020202020000018bcfe56800000004030000
+openssl req -x509 -new -newkey ec -pkeyopt ec_paramgen_curve:prime256v1 -nodes \
+    -keyout "$tmp/sct.key" -days 3650 -sha256 \
+    -subj "/CN=sct.example.com" \
+    -addext "basicConstraints=critical,CA:FALSE" \
+    -addext "1.3.6.1.4.1.11129.2.4.2=DER:$sct_list" \
+    -outform DER -out sct.der
+
+# Ed25519 self-signed certificate, with subject attributes missing from the x509-parser OID table
+openssl req -x509 -new -newkey ed25519 -nodes -keyout "$tmp/ed25519.key" -days 3650 \
+    -subj "/CN=ed25519.example.com/serialNumber=1234/businessCategory=Private Organization" \
+    -addext "basicConstraints=critical,CA:FALSE" \
+    -addext "subjectAltName=DNS:ed25519.example.com" \
+    -outform DER -out ed25519.der
+
+openssl req -x509 -new -newkey ed448 -nodes -keyout "$tmp/ed448.key" -days 3650 \
+    -subj "/CN=ed448.example.com" \
+    -addext "basicConstraints=critical,CA:FALSE" \
+    -addext "subjectAltName=DNS:ed448.example.com" \
+    -outform DER -out ed448.der